@@ -0,0 +1,124 @@
+use lzw_with_universal_coder::bits::Bits;
+use lzw_with_universal_coder::universal_coding::{UniversalCode, Creatable};
+use lzw_with_universal_coder::elias_gamma::EliasGamma;
+use lzw_with_universal_coder::elias_delta::EliasDelta;
+use lzw_with_universal_coder::elias_omega::EliasOmega;
+use lzw_with_universal_coder::fibonacci::Fibonacci;
+use std::time::Instant;
+
+/*
+    Bit by bit decoding over `Bits::get`, as `UniversalCode::get` worked before table-driven decoders
+*/
+
+fn baseline_gamma(data: &Bits, index: &mut usize) -> Option<u64> {
+    let mut t = 1_u64;
+    let mut res = 0;
+    while !data.get(*index)? {
+        *index += 1;
+        t <<= 1;
+    }
+    t <<= 1;
+    while t > 1 {
+        t >>= 1;
+        if data.get(*index)? {
+            res += t;
+        }
+        *index += 1;
+    }
+    Some(res - 1)
+}
+
+fn baseline_delta(data: &Bits, index: &mut usize) -> Option<u64> {
+    let n = baseline_gamma(data, index)? + 1;
+    let mut t = 1_u64 << (n - 1);
+    let mut res = t;
+    while t > 1 {
+        t >>= 1;
+        if data.get(*index)? {
+            res += t;
+        }
+        *index += 1;
+    }
+    Some(res - 1)
+}
+
+fn baseline_omega(data: &Bits, index: &mut usize) -> Option<u64> {
+    let mut n = 1_u64;
+    while data.get(*index)? {
+        let mut t = 1 << n;
+        *index += 1;
+        n = t;
+        t >>= 1;
+        while t > 0 {
+            if data.get(*index)? {
+                n += t;
+            }
+            *index += 1;
+            t >>= 1;
+        }
+    }
+    *index += 1;
+    Some(n - 1)
+}
+
+const FIB: [u64; 93] = {
+    let mut fib = [1_u64; 93];
+    let mut i = 2;
+    while i < fib.len() {
+        fib[i] = fib[i - 1] + fib[i - 2];
+        i += 1;
+    }
+    fib
+};
+
+fn baseline_fibonacci(data: &Bits, index: &mut usize) -> Option<u64> {
+    let mut n = 0;
+    let mut res = 0_u64;
+    let mut prev = false;
+    loop {
+        let x = data.get(*index)?;
+        *index += 1;
+        n += 1;
+        if x {
+            if prev {
+                return Some(res - 1);
+            }
+            res += FIB[n];
+        }
+        prev = x;
+    }
+}
+
+fn measure<F: FnMut() -> Option<u64>>(mut next: F, count: usize, bytes: usize) -> f64 {
+    let start = Instant::now();
+    let mut sum = 0_u64;
+    for _ in 0..count {
+        sum = sum.wrapping_add(next().unwrap());
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    assert_ne!(sum, 1);
+    bytes as f64 / seconds / 1_000_000.0
+}
+
+fn bench<X: UniversalCode + Creatable>(name: &str, baseline: fn(&Bits, &mut usize) -> Option<u64>, numbers: &[u64]) {
+    let mut code = X::new();
+    code.encode_slice(numbers);
+    let data = code.to_bits();
+    let bytes = data.bytes().len();
+    let mut index = 0;
+    let before = measure(|| baseline(&data, &mut index), numbers.len(), bytes);
+    let mut code = X::from_bits(data);
+    let after = measure(|| code.get(), numbers.len(), bytes);
+    println!("{:<10} baseline get {:>8.2} MB/s   get {:>8.2} MB/s", name, before, after);
+}
+
+fn main() {
+    // Indices similar to LZW output: growing dictionary, mostly recent entries
+    let numbers: Vec<u64> = (0..2_000_000_u64)
+        .map(|i| (i.wrapping_mul(0x9e3779b97f4a7c15) >> 40) % (256 + i / 4))
+        .collect();
+    bench::<EliasGamma>("gamma", baseline_gamma, &numbers);
+    bench::<EliasDelta>("delta", baseline_delta, &numbers);
+    bench::<EliasOmega>("omega", baseline_omega, &numbers);
+    bench::<Fibonacci>("fibonacci", baseline_fibonacci, &numbers);
+}
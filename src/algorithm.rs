@@ -0,0 +1,218 @@
+use crate::dictionary::Dictionary;
+use crate::lzap;
+use crate::lzmw;
+use crate::lzw;
+use std::marker::PhantomData;
+
+/**
+    Dictionary update rule turning bytes into indices, stored in file as one byte id
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    #[default]
+    Lzw,
+    Lzmw,
+    Lzap,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 3] = [Algorithm::Lzw, Algorithm::Lzmw, Algorithm::Lzap];
+
+    pub fn id(self) -> u8 {
+        match self {
+            Algorithm::Lzw => 0,
+            Algorithm::Lzmw => 1,
+            Algorithm::Lzap => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|a| a.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Lzw => "lzw",
+            Algorithm::Lzmw => "lzmw",
+            Algorithm::Lzap => "lzap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    /**
+        Indices of `data` starting from given dictionary, which is left in its final state
+    */
+    pub fn encode<F: FnMut(u32)>(self, data: &[u8], dictionary: &mut Dictionary, progress: F) -> Vec<u64> {
+        match self {
+            Algorithm::Lzw => lzw::encode_with_dictionary(data, dictionary, progress),
+            Algorithm::Lzmw => lzmw::encode_with_dictionary(data, dictionary, progress),
+            Algorithm::Lzap => lzap::encode_with_dictionary(data, dictionary, progress),
+        }
+    }
+
+    pub fn decoder(self, dictionary: Dictionary) -> Decoder {
+        match self {
+            Algorithm::Lzw => Decoder::Lzw(lzw::Decoder::with_dictionary(dictionary)),
+            Algorithm::Lzmw => Decoder::Lzmw(lzmw::Decoder::with_dictionary(dictionary)),
+            Algorithm::Lzap => Decoder::Lzap(lzap::Decoder::with_dictionary(dictionary)),
+        }
+    }
+
+    pub fn decode<I: IntoIterator<Item=u64>>(self, codes: I) -> Result<Vec<u8>, String> {
+        let mut decoder = self.decoder(Dictionary::new());
+        let mut res = vec![];
+        for code in codes {
+            decoder.push(code, &mut res)?;
+        }
+        Ok(res)
+    }
+}
+
+/**
+    Decoder of any algorithm
+*/
+#[derive(Debug)]
+pub enum Decoder {
+    Lzw(lzw::Decoder),
+    Lzmw(lzmw::Decoder),
+    Lzap(lzap::Decoder),
+}
+
+impl Decoder {
+    /**
+        Append bytes of given index to `res`
+    */
+    pub fn push(&mut self, code: u64, res: &mut Vec<u8>) -> Result<(), String> {
+        match self {
+            Decoder::Lzw(d) => d.push(code, res),
+            Decoder::Lzmw(d) => d.push(code, res),
+            Decoder::Lzap(d) => d.push(code, res),
+        }
+    }
+
+    pub fn dictionary(&self) -> &Dictionary {
+        match self {
+            Decoder::Lzw(d) => d.dictionary(),
+            Decoder::Lzmw(d) => d.dictionary(),
+            Decoder::Lzap(d) => d.dictionary(),
+        }
+    }
+}
+
+/**
+    Dictionary update of algorithms which parse data into the longest words of dictionary,
+    called with the previous and the current phrase
+*/
+pub trait PhraseRule {
+    const NAME: &'static str;
+    fn update(dictionary: &mut Dictionary, prev: &[u8], phrase: &[u8]);
+}
+
+/**
+    Parse data into the longest words of dictionary, updating it after every phrase
+    but the first one. `progress` is called with every percent of processed data
+*/
+pub fn encode_phrases<R: PhraseRule, F: FnMut(u32)>(data: &[u8], dictionary: &mut Dictionary, mut progress: F) -> Vec<u64> {
+    let mut res = vec![];
+    let mut prev: &[u8] = &[];
+    let mut position = 0;
+    let mut percent = 0;
+    while position < data.len() {
+        while (position * 100) / data.len() >= percent {
+            progress(percent as u32);
+            percent += 1;
+        }
+        // Single bytes are always in dictionary
+        let (index, len) = dictionary.longest_match(&data[position..]).unwrap();
+        dictionary.mark_used(index);
+        res.push(index as u64);
+        let phrase = &data[position..position + len];
+        if !prev.is_empty() {
+            R::update(dictionary, prev, phrase);
+        }
+        prev = phrase;
+        position += len;
+    }
+    res
+}
+
+/**
+    Updates dictionary the same way as encoder right after every decoded phrase
+*/
+#[derive(Debug)]
+pub struct PhraseDecoder<R> {
+    dictionary: Dictionary,
+    prev: Vec<u8>,
+    rule: PhantomData<R>,
+}
+
+impl<R: PhraseRule> Default for PhraseDecoder<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: PhraseRule> PhraseDecoder<R> {
+    pub fn new() -> Self {
+        Self::with_dictionary(Dictionary::new())
+    }
+
+    pub fn with_dictionary(dictionary: Dictionary) -> Self {
+        Self {
+            dictionary,
+            prev: vec![],
+            rule: PhantomData,
+        }
+    }
+
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    /**
+        Append bytes of given index to `res`
+    */
+    pub fn push(&mut self, code: u64, res: &mut Vec<u8>) -> Result<(), String> {
+        if code >= self.dictionary.len() as u64 {
+            return Err(format!("Invalid {} code {}", R::NAME, code));
+        }
+        self.dictionary.mark_used(code as usize);
+        let word = self.dictionary.word(code as usize);
+        res.extend_from_slice(&word);
+        if !self.prev.is_empty() {
+            R::update(&mut self.dictionary, &self.prev, &word);
+        }
+        self.prev = word;
+        Ok(())
+    }
+}
+
+pub fn decode_phrases<R: PhraseRule, I: IntoIterator<Item=u64>>(codes: I) -> Result<Vec<u8>, String> {
+    let mut decoder = PhraseDecoder::<R>::new();
+    let mut res = vec![];
+    for code in codes {
+        decoder.push(code, &mut res)?;
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod algorithm_test {
+    use super::Algorithm;
+    use crate::dictionary::Dictionary;
+
+    #[test]
+    fn algorithm_test() {
+        let data = b"abracadabra abracadabra abracadabra".to_vec();
+        for algorithm in Algorithm::ALL.iter() {
+            assert_eq!(Algorithm::from_id(algorithm.id()), Some(*algorithm));
+            assert_eq!(Algorithm::from_name(algorithm.name()), Some(*algorithm));
+            let codes = algorithm.encode(&data, &mut Dictionary::new(), |_| {});
+            assert_eq!(algorithm.decode(codes), Ok(data.clone()));
+        }
+        assert_eq!(Algorithm::from_name("lz77"), None);
+    }
+}
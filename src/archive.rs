@@ -0,0 +1,483 @@
+use crate::bits::Bits;
+use crate::container::{CodeType, Section};
+use crate::lzw;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/**
+    One compressed file of an archive, path is relative and uses `/` as separator
+*/
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub path: String,
+    pub size: u64,
+    /**
+        Seconds since Unix epoch
+    */
+    pub mtime: u64,
+    pub mode: u32,
+    pub code: CodeType,
+    pub data: Bits,
+}
+
+impl Member {
+    /**
+        Content of member of non solid archive, members of solid archive have no own bits
+        and are decompressed all at once with `Archive::decompress_all`
+    */
+    pub fn decompress(&self) -> Result<Vec<u8>, String> {
+        if self.data.is_empty() && self.size > 0 {
+            return Err(format!("Member {} is in solid stream, use Archive::decompress_all", self.path));
+        }
+        let mut coder = self.code.from_bits(self.data.clone());
+        let res = lzw::decode(std::iter::from_fn(|| coder.get()))?;
+        if res.len() as u64 != self.size {
+            return Err(format!("Damaged member {}", self.path));
+        }
+        Ok(res)
+    }
+}
+
+/**
+    File read from disk, before compression
+*/
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: String,
+    pub content: Vec<u8>,
+    pub mtime: u64,
+    pub mode: u32,
+}
+
+/**
+    File format: magic bytes, version, flags and number of members (u64).
+    Member is stored as path length (u32) and UTF-8 path, size, modification time (u64),
+    permissions (u32), coding id (u8), number of bits (u64) and the bits.
+    Solid archive (lowest bit of flags) has members without bits, followed by
+    one stream of all files: coding id, number of bits and the bits.
+    All numbers are little endian.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Archive {
+    pub members: Vec<Member>,
+    /**
+        LZW codes of all files one after another, so dictionary carries over between files
+    */
+    pub solid: Option<Section>,
+}
+
+/**
+    Reads numbers of the archive header, every read checks remaining length
+*/
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.position < n {
+            return Err("Truncated archive".to_string());
+        }
+        self.position += n;
+        Ok(&self.bytes[self.position - n..self.position])
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut res = [0_u8; 8];
+        res.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(res))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut res = [0_u8; 4];
+        res.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(res))
+    }
+
+    /**
+        Coding id, number of bits and the bits
+    */
+    fn section(&mut self) -> Result<(CodeType, Bits), String> {
+        let id = self.take(1)?[0];
+        let code = match CodeType::from_id(id) {
+            Some(c) => c,
+            None => return Err(format!("Unknown coding {}", id)),
+        };
+        let bits = self.u64()? as usize;
+        let data = self.take(bits.div_ceil(8))?.to_vec();
+        Ok((code, Bits::from_bytes(data, bits)))
+    }
+}
+
+impl Archive {
+    pub const MAGIC: [u8; 4] = *b"LZWA";
+    pub const VERSION: u8 = 1;
+    const SOLID: u8 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            members: Vec::new(),
+            solid: None,
+        }
+    }
+
+    /**
+        Compress all files as one LZW stream, `None` as coding selects the smallest coding
+    */
+    pub fn new_solid(entries: &[Entry], code: Option<CodeType>) -> Self {
+        let content: Vec<u8> = entries.iter().flat_map(|e| e.content.iter().copied()).collect();
+        let codes = lzw::encode(&content);
+        let (code, coded) = match code {
+            Some(c) => (c, c.encode(&codes)),
+            None => CodeType::best(&codes),
+        };
+        let members = entries.iter().map(|e| Member {
+            path: e.path.clone(),
+            size: e.content.len() as u64,
+            mtime: e.mtime,
+            mode: e.mode,
+            code,
+            data: Bits::new(),
+        }).collect();
+        Self {
+            members,
+            solid: Some(Section {
+                code,
+                data: coded.to_bits(),
+            }),
+        }
+    }
+
+    /**
+        Content of every member, in order of members
+    */
+    pub fn decompress_all(&self) -> Result<Vec<Vec<u8>>, String> {
+        let solid = match &self.solid {
+            Some(s) => s,
+            None => return self.members.iter().map(|m| m.decompress()).collect(),
+        };
+        let total = self.members.iter()
+            .try_fold(0_u64, |total, m| total.checked_add(m.size))
+            .ok_or_else(|| "Damaged archive".to_string())?;
+        let mut coder = solid.code.from_bits(solid.data.clone());
+        let content = lzw::decode(std::iter::from_fn(|| coder.get()))?;
+        if total != content.len() as u64 {
+            return Err("Damaged solid archive".to_string());
+        }
+        let mut position = 0;
+        let mut res = vec![];
+        for m in self.members.iter() {
+            res.push(content[position..position + m.size as usize].to_vec());
+            position += m.size as usize;
+        }
+        Ok(res)
+    }
+
+    /**
+        Compress file content, `None` as coding selects the smallest coding
+    */
+    pub fn add(&mut self, path: String, content: &[u8], mtime: u64, mode: u32, code: Option<CodeType>) {
+        let codes = lzw::encode(content);
+        let (code, coded) = match code {
+            Some(c) => (c, c.encode(&codes)),
+            None => CodeType::best(&codes),
+        };
+        self.members.push(Member {
+            path,
+            size: content.len() as u64,
+            mtime,
+            mode,
+            code,
+            data: coded.to_bits(),
+        });
+    }
+
+    pub fn is_archive(bytes: &[u8]) -> bool {
+        bytes.len() > Self::MAGIC.len() && bytes[..Self::MAGIC.len()] == Self::MAGIC
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Self::MAGIC.to_vec();
+        res.push(Self::VERSION);
+        res.push(if self.solid.is_some() { Self::SOLID } else { 0 });
+        res.extend_from_slice(&(self.members.len() as u64).to_le_bytes());
+        for member in self.members.iter() {
+            res.extend_from_slice(&(member.path.len() as u32).to_le_bytes());
+            res.extend_from_slice(member.path.as_bytes());
+            res.extend_from_slice(&member.size.to_le_bytes());
+            res.extend_from_slice(&member.mtime.to_le_bytes());
+            res.extend_from_slice(&member.mode.to_le_bytes());
+            res.push(member.code.id());
+            res.extend_from_slice(&(member.data.len() as u64).to_le_bytes());
+            res.extend_from_slice(member.data.bytes());
+        }
+        if let Some(solid) = &self.solid {
+            res.push(solid.code.id());
+            res.extend_from_slice(&(solid.data.len() as u64).to_le_bytes());
+            res.extend_from_slice(solid.data.bytes());
+        }
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !Self::is_archive(bytes) {
+            return Err("Missing archive header".to_string());
+        }
+        let mut reader = ByteReader {
+            bytes,
+            position: Self::MAGIC.len(),
+        };
+        let header = reader.take(2)?;
+        if header[0] != Self::VERSION {
+            return Err(format!("Unsupported archive version {}", header[0]));
+        }
+        let count = reader.u64()?;
+        let mut res = Self::new();
+        for _ in 0..count {
+            let length = reader.u32()? as usize;
+            let path = match String::from_utf8(reader.take(length)?.to_vec()) {
+                Ok(p) => p,
+                Err(_e) => return Err("Member path is not UTF-8".to_string()),
+            };
+            let size = reader.u64()?;
+            let mtime = reader.u64()?;
+            let mode = reader.u32()?;
+            let (code, data) = reader.section()?;
+            res.members.push(Member {
+                path,
+                size,
+                mtime,
+                mode,
+                code,
+                data,
+            });
+        }
+        if header[1] & Self::SOLID != 0 {
+            let (code, data) = reader.section()?;
+            res.solid = Some(Section { code, data });
+        }
+        Ok(res)
+    }
+
+    /**
+        Add files and whole directories, member paths start with the last component of given path.
+        Returns descriptions of skipped entries
+    */
+    pub fn add_path<X: AsRef<Path>>(&mut self, path: X, code: Option<CodeType>) -> Result<Vec<String>, String> {
+        let (entries, skipped) = Self::read_path(path)?;
+        for e in entries {
+            self.add(e.path, &e.content, e.mtime, e.mode, code);
+        }
+        Ok(skipped)
+    }
+
+    /**
+        Read file or all files of directory, along with descriptions of entries which
+        can not be stored: symbolic links, special files and empty directories.
+        Paths like `.` and `..` have no name, so their members start with their content
+    */
+    pub fn read_path<X: AsRef<Path>>(path: X) -> Result<(Vec<Entry>, Vec<String>), String> {
+        let path = path.as_ref();
+        let name = path.file_name().map(PathBuf::from).unwrap_or_default();
+        let mut res = vec![];
+        let mut skipped = vec![];
+        Self::read_tree(path, &name, &mut res, &mut skipped)?;
+        Ok((res, skipped))
+    }
+
+    fn read_tree(path: &Path, name: &Path, res: &mut Vec<Entry>, skipped: &mut Vec<String>) -> Result<(), String> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(m) => m,
+            Err(_e) => return Err(format!("Unable to read {}", path.display())),
+        };
+        if metadata.is_dir() {
+            let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+                Ok(dir) => dir.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+                Err(_e) => return Err(format!("Unable to read directory {}", path.display())),
+            };
+            entries.sort();
+            if entries.is_empty() {
+                skipped.push(format!("{} (empty directory)", path.display()));
+            }
+            for entry in entries {
+                let entry_name = name.join(entry.file_name().unwrap());
+                Self::read_tree(&entry, &entry_name, res, skipped)?;
+            }
+        } else if metadata.is_file() {
+            let content = match fs::read(path) {
+                Ok(c) => c,
+                Err(_e) => return Err(format!("Unable to read file {}", path.display())),
+            };
+            let mtime = metadata.modified().ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let components: Vec<String> = name.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            res.push(Entry {
+                path: components.join("/"),
+                content,
+                mtime,
+                mode: Self::mode(&metadata),
+            });
+        } else if metadata.file_type().is_symlink() {
+            skipped.push(format!("{} (symbolic link)", path.display()));
+        } else {
+            skipped.push(format!("{} (special file)", path.display()));
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn mode(metadata: &fs::Metadata) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o7777
+    }
+
+    #[cfg(not(unix))]
+    fn mode(metadata: &fs::Metadata) -> u32 {
+        if metadata.permissions().readonly() { 0o444 } else { 0o644 }
+    }
+
+    /**
+        Member path inside of `dir`, paths leaving it are refused
+    */
+    pub fn member_path<X: AsRef<Path>>(dir: X, member: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(member);
+        if member.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Unsafe member path {}", member));
+        }
+        Ok(dir.as_ref().join(relative))
+    }
+
+    /**
+        Write all members into given directory with their permissions and modification times
+    */
+    pub fn extract_to<X: AsRef<Path>>(&self, dir: X) -> Result<(), String> {
+        for member in self.members.iter() {
+            Self::member_path(&dir, &member.path)?;
+        }
+        for (member, content) in self.members.iter().zip(self.decompress_all()?) {
+            let path = Self::member_path(&dir, &member.path)?;
+            if let Some(parent) = path.parent() {
+                if fs::create_dir_all(parent).is_err() {
+                    return Err(format!("Unable to create directory {}", parent.display()));
+                }
+            }
+            let write = File::create(&path).and_then(|mut file| {
+                file.write_all(&content)?;
+                file.set_modified(UNIX_EPOCH + Duration::from_secs(member.mtime))?;
+                file.sync_all()
+            });
+            if write.is_err() {
+                return Err(format!("Unable to write file {}", path.display()));
+            }
+            Self::set_mode(&path, member.mode)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_mode(path: &Path, mode: u32) -> Result<(), String> {
+        use std::os::unix::fs::PermissionsExt;
+        match fs::set_permissions(path, fs::Permissions::from_mode(mode)) {
+            Ok(()) => Ok(()),
+            Err(_e) => Err(format!("Unable to set permissions of {}", path.display())),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod archive_test {
+    use super::{Archive, Entry};
+    use crate::container::CodeType;
+    use std::fs;
+
+    #[test]
+    fn archive_test() {
+        let mut archive = Archive::new();
+        archive.add("a.txt".to_string(), b"abcabcabcabc", 1600000000, 0o644, Some(CodeType::Omega));
+        archive.add("dir/b.bin".to_string(), &[0, 1, 2, 255, 0, 1, 2], 7, 0o755, None);
+        archive.add("dir/empty".to_string(), b"", 0, 0o600, Some(CodeType::Arithmetic));
+        let read = Archive::from_bytes(&archive.to_bytes()).unwrap();
+        assert_eq!(read.members.len(), 3);
+        assert_eq!(read.members[1].path, "dir/b.bin");
+        assert_eq!(read.members[1].mtime, 7);
+        assert_eq!(read.members[1].mode, 0o755);
+        assert_eq!(read.members[0].decompress().unwrap(), b"abcabcabcabc");
+        assert_eq!(read.members[1].decompress().unwrap(), [0, 1, 2, 255, 0, 1, 2]);
+        assert_eq!(read.members[2].decompress().unwrap(), b"");
+        let bytes = archive.to_bytes();
+        assert!(Archive::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Archive::member_path("out", "../etc/passwd").is_err());
+        assert!(Archive::member_path("out", "/etc/passwd").is_err());
+        assert!(Archive::member_path("out", "a/./b").is_ok());
+    }
+
+    #[test]
+    fn files_test() {
+        let root = std::env::temp_dir().join(format!("lzw_archive_test_{}", std::process::id()));
+        let source = root.join("source");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("one.txt"), b"first file first file").unwrap();
+        fs::write(source.join("nested").join("two.txt"), b"second").unwrap();
+        let mut archive = Archive::new();
+        assert_eq!(archive.add_path(&source, Some(CodeType::Gamma)), Ok(vec![]));
+        let paths: Vec<&str> = archive.members.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, ["source/nested/two.txt", "source/one.txt"]);
+        let target = root.join("target");
+        Archive::from_bytes(&archive.to_bytes()).unwrap().extract_to(&target).unwrap();
+        assert_eq!(fs::read(target.join("source").join("one.txt")).unwrap(), b"first file first file");
+        assert_eq!(fs::read(target.join("source").join("nested").join("two.txt")).unwrap(), b"second");
+        let original = fs::metadata(source.join("one.txt")).unwrap().modified().unwrap();
+        let extracted = fs::metadata(target.join("source").join("one.txt")).unwrap().modified().unwrap();
+        assert_eq!(
+            original.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+            extracted.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+        );
+        fs::create_dir_all(source.join("empty")).unwrap();
+        let (entries, skipped) = Archive::read_path(source.join("nested").join("..")).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["nested/two.txt", "one.txt"]);
+        assert_eq!(skipped.len(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn solid_test() {
+        let entries: Vec<Entry> = (0..20).map(|i| Entry {
+            path: format!("config{}.json", i),
+            content: format!("{{\"name\": \"service{}\", \"port\": {}, \"enabled\": true}}", i, 8000 + i).into_bytes(),
+            mtime: i,
+            mode: 0o644,
+        }).collect();
+        let solid = Archive::new_solid(&entries, Some(CodeType::Delta));
+        let mut separate = Archive::new();
+        for e in entries.iter() {
+            separate.add(e.path.clone(), &e.content, e.mtime, e.mode, Some(CodeType::Delta));
+        }
+        assert!(solid.to_bytes().len() < separate.to_bytes().len());
+        let read = Archive::from_bytes(&solid.to_bytes()).unwrap();
+        assert!(read.solid.is_some());
+        let contents = read.decompress_all().unwrap();
+        assert!(read.members[0].decompress().unwrap_err().contains("solid"));
+        for (e, (m, c)) in entries.iter().zip(read.members.iter().zip(contents)) {
+            assert_eq!(m.path, e.path);
+            assert_eq!(m.mtime, e.mtime);
+            assert_eq!(c, e.content);
+        }
+        let mut damaged = Archive::from_bytes(&solid.to_bytes()).unwrap();
+        damaged.members[0].size = u64::MAX;
+        assert_eq!(damaged.decompress_all(), Err("Damaged archive".to_string()));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::bits::Bits;
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect};
+use crate::unsigned::Unsigned;
+use std::path::Path;
+
+/**
+    Adaptive frequency model, by default over the bit length of coded numbers.
+    Symbol 0 marks the end of the stream, symbols 1..=65 are bit lengths.
+*/
+#[derive(Debug, Clone)]
+struct Model {
+    freq: Vec<u32>,
+    total: u32,
+}
+
+impl Model {
+    const SYMBOLS: usize = 66;
+    const EOF: usize = 0;
+    const STEP: u32 = 32;
+    const MAX_TOTAL: u32 = 1 << 16;
+
+    fn new() -> Self {
+        Self::with_symbols(Self::SYMBOLS)
+    }
+
+    fn with_symbols(symbols: usize) -> Self {
+        Self {
+            freq: vec![1; symbols],
+            total: symbols as u32,
+        }
+    }
+
+    fn range(&self, symbol: usize) -> (u32, u32) {
+        let low = self.freq[..symbol].iter().sum::<u32>();
+        (low, low + self.freq[symbol])
+    }
+
+    fn symbol(&self, target: u32) -> (usize, u32, u32) {
+        let mut low = 0;
+        for (symbol, f) in self.freq.iter().enumerate() {
+            if target < low + f {
+                return (symbol, low, low + f);
+            }
+            low += f;
+        }
+        unreachable!("target outside of model range")
+    }
+
+    fn update(&mut self, symbol: usize) {
+        self.freq[symbol] += Self::STEP;
+        self.total += Self::STEP;
+        if self.total > Self::MAX_TOTAL {
+            self.total = 0;
+            for f in self.freq.iter_mut() {
+                *f = f.div_ceil(2);
+                self.total += *f;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Encoder {
+    low: u64,
+    high: u64,
+    pending: u64,
+}
+
+impl Encoder {
+    fn new() -> Self {
+        Self {
+            low: 0,
+            high: Arithmetic::TOP,
+            pending: 0,
+        }
+    }
+
+    fn emit(&mut self, data: &mut Bits, bit: bool) {
+        data.push(bit);
+        for _ in 0..self.pending {
+            data.push(!bit);
+        }
+        self.pending = 0;
+    }
+
+    fn encode(&mut self, data: &mut Bits, low: u32, high: u32, total: u32) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + range * high as u64 / total as u64 - 1;
+        self.low += range * low as u64 / total as u64;
+        loop {
+            if self.high < Arithmetic::HALF {
+                self.emit(data, false);
+            } else if self.low >= Arithmetic::HALF {
+                self.emit(data, true);
+                self.low -= Arithmetic::HALF;
+                self.high -= Arithmetic::HALF;
+            } else if self.low >= Arithmetic::QUARTER && self.high < 3 * Arithmetic::QUARTER {
+                self.pending += 1;
+                self.low -= Arithmetic::QUARTER;
+                self.high -= Arithmetic::QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+        }
+    }
+
+    fn finish(&mut self, data: &mut Bits) {
+        self.pending += 1;
+        let bit = self.low >= Arithmetic::QUARTER;
+        self.emit(data, bit);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Decoder {
+    low: u64,
+    high: u64,
+    value: u64,
+}
+
+impl Decoder {
+    fn new(data: &Bits, index: &mut usize) -> Self {
+        let mut value = 0;
+        for _ in 0..Arithmetic::PRECISION {
+            value = (value << 1) | Self::next_bit(data, index);
+        }
+        Self {
+            low: 0,
+            high: Arithmetic::TOP,
+            value,
+        }
+    }
+
+    fn next_bit(data: &Bits, index: &mut usize) -> u64 {
+        let bit = data.get(*index).unwrap_or(false);
+        *index += 1;
+        bit as u64
+    }
+
+    fn target(&self, total: u32) -> u32 {
+        let range = self.high - self.low + 1;
+        (((self.value - self.low + 1) * total as u64 - 1) / range) as u32
+    }
+
+    fn decode(&mut self, data: &Bits, index: &mut usize, low: u32, high: u32, total: u32) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + range * high as u64 / total as u64 - 1;
+        self.low += range * low as u64 / total as u64;
+        loop {
+            if self.high < Arithmetic::HALF {
+            } else if self.low >= Arithmetic::HALF {
+                self.value -= Arithmetic::HALF;
+                self.low -= Arithmetic::HALF;
+                self.high -= Arithmetic::HALF;
+            } else if self.low >= Arithmetic::QUARTER && self.high < 3 * Arithmetic::QUARTER {
+                self.value -= Arithmetic::QUARTER;
+                self.low -= Arithmetic::QUARTER;
+                self.high -= Arithmetic::QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+            self.value = (self.value << 1) | Self::next_bit(data, index);
+        }
+    }
+}
+
+/**
+    Bytes coded with order-0 adaptive model of all 256 values,
+    number of bytes is not stored
+*/
+pub fn encode_bytes(bytes: &[u8]) -> Bits {
+    let mut data = Bits::new();
+    let mut model = Model::with_symbols(256);
+    let mut encoder = Encoder::new();
+    for b in bytes.iter() {
+        let (low, high) = model.range(*b as usize);
+        encoder.encode(&mut data, low, high, model.total);
+        model.update(*b as usize);
+    }
+    encoder.finish(&mut data);
+    data
+}
+
+/**
+    First `count` bytes coded by `encode_bytes`, `None` when data ends before them
+*/
+pub fn decode_bytes(data: &Bits, count: usize) -> Option<Vec<u8>> {
+    let mut index = 0;
+    let mut model = Model::with_symbols(256);
+    let mut decoder = Decoder::new(data, &mut index);
+    let mut res = vec![];
+    for _ in 0..count {
+        let (symbol, low, high) = model.symbol(decoder.target(model.total));
+        decoder.decode(data, &mut index, low, high, model.total);
+        model.update(symbol);
+        if index > data.len() + Arithmetic::PRECISION as usize {
+            return None;
+        }
+        res.push(symbol as u8);
+    }
+    Some(res)
+}
+
+/**
+    Adaptive arithmetic coder for LZW indices.
+    The bit length of every number is coded with an adaptive model,
+    the remaining low bits are coded with uniform probability.
+    Stream is terminated with an end symbol, so it has to be finished
+    before reading, which happens on the first `get`. Numbers added afterwards
+    start a new segment with fresh model, read after the end symbol.
+*/
+#[derive(Debug)]
+pub struct Arithmetic {
+    data: Bits,
+    index: usize,
+    model: Model,
+    encoder: Encoder,
+    decoder: Option<(Decoder, Model)>,
+    finished: bool,
+}
+
+impl Arithmetic {
+    const PRECISION: u32 = 32;
+    const TOP: u64 = (1 << Self::PRECISION) - 1;
+    const HALF: u64 = 1 << (Self::PRECISION - 1);
+    const QUARTER: u64 = 1 << (Self::PRECISION - 2);
+    const RAW_CHUNK: u32 = 16;
+
+    pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
+    }
+
+    fn encode_symbol(&mut self, symbol: usize) {
+        let (low, high) = self.model.range(symbol);
+        self.encoder.encode(&mut self.data, low, high, self.model.total);
+        self.model.update(symbol);
+    }
+
+    /**
+        Write end of segment, next number starts a new one
+    */
+    fn close(&mut self) {
+        if !self.finished {
+            self.encode_symbol(Model::EOF);
+            self.encoder.finish(&mut self.data);
+            self.finished = true;
+        }
+    }
+
+    fn closed_data(&self) -> Bits {
+        let mut data = self.data.clone();
+        if !self.finished {
+            let mut encoder = self.encoder.clone();
+            let (low, high) = self.model.range(Model::EOF);
+            encoder.encode(&mut data, low, high, self.model.total);
+            encoder.finish(&mut data);
+        }
+        data
+    }
+}
+
+impl Creatable for Arithmetic {
+    fn new() -> Self {
+        Self {
+            data: Bits::new(),
+            index: 0,
+            model: Model::new(),
+            encoder: Encoder::new(),
+            decoder: None,
+            finished: false,
+        }
+    }
+
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
+            model: Model::new(),
+            encoder: Encoder::new(),
+            decoder: None,
+            finished: true,
+        }
+    }
+}
+
+impl UniversalCode for Arithmetic {
+    fn get(&mut self) -> Option<u64> {
+        self.close();
+        let (mut decoder, model, symbol) = loop {
+            let (mut decoder, mut model) = match self.decoder.take() {
+                Some(d) => d,
+                None if self.index < self.data.len() => (Decoder::new(&self.data, &mut self.index), Model::new()),
+                None => return None,
+            };
+            let (symbol, low, high) = model.symbol(decoder.target(model.total));
+            decoder.decode(&self.data, &mut self.index, low, high, model.total);
+            model.update(symbol);
+            // Damaged stream without end symbol would never stop
+            if self.index > self.data.len() + Self::PRECISION as usize {
+                self.index = self.data.len();
+                return None;
+            }
+            if symbol != Model::EOF {
+                break (decoder, model, symbol);
+            }
+            // Segment has two bits more than shifts of the coder, decoder reads PRECISION bits ahead
+            self.index = self.index + 2 - Self::PRECISION as usize;
+        };
+        let mut res = 0_u64;
+        let mut left = symbol as u32 - 1;
+        while left > 0 {
+            let chunk = left.min(Self::RAW_CHUNK);
+            let total = 1_u32 << chunk;
+            let value = decoder.target(total);
+            decoder.decode(&self.data, &mut self.index, value, value + 1, total);
+            res = (res << chunk) | value as u64;
+            left -= chunk;
+        }
+        self.decoder = Some((decoder, model));
+        u64::from_successor(symbol as u32 - 1, res as u128)
+    }
+
+    fn add(&mut self, code: u64) {
+        if self.finished {
+            self.model = Model::new();
+            self.encoder = Encoder::new();
+            self.finished = false;
+        }
+        let n = code.successor_size();
+        let code = code.wrapping_add(1);
+        self.encode_symbol(n as usize);
+        let mut left = n - 1;
+        while left > 0 {
+            let chunk = left.min(Self::RAW_CHUNK);
+            left -= chunk;
+            let value = ((code >> left) & ((1 << chunk) - 1)) as u32;
+            self.encoder.encode(&mut self.data, value, value + 1, 1 << chunk);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.close();
+    }
+
+    fn save_to_file(&self, path: String) -> Result<(), String> {
+        self.closed_data().save_to_file(path)
+    }
+
+    fn to_bits(&self) -> Bits {
+        self.closed_data()
+    }
+
+    fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
+        Box::new(UniversalCodeIter {
+            c: self
+        })
+    }
+
+    fn len(&self) -> usize {
+        if self.finished {
+            self.data.len()
+        } else {
+            self.closed_data().len()
+        }
+    }
+
+    fn index(&self) -> usize {
+        self.index.min(self.data.len())
+    }
+
+    fn entropy(&self) -> f64 {
+        self.closed_data().entropy()
+    }
+}
+
+impl_collect!(Arithmetic);
+
+#[cfg(test)]
+mod arithmetic_test {
+    use crate::universal_coding::{UniversalCode, Creatable, boundary_values};
+
+    #[test]
+    fn arithmetic_test() {
+        let mut c = super::Arithmetic::new();
+        c.add(7);
+        c.add(15);
+        c.add(31);
+        c.add(0);
+        c.add(1323123213123);
+        c.add(3312312345324423);
+        assert_eq!(c.get(), Some(7));
+        assert_eq!(c.get(), Some(15));
+        assert_eq!(c.get(), Some(31));
+        assert_eq!(c.get(), Some(0));
+        assert_eq!(c.get(), Some(1323123213123));
+        assert_eq!(c.get(), Some(3312312345324423));
+        assert_eq!(c.get(), None);
+        assert_eq!(c.get(), None);
+    }
+
+    #[test]
+    fn add_after_get_test() {
+        let mut c = super::Arithmetic::new();
+        c.add(7);
+        assert_eq!(c.get(), Some(7));
+        c.add(15);
+        c.add(31);
+        assert_eq!(c.get(), Some(15));
+        assert_eq!(c.get(), Some(31));
+        assert_eq!(c.get(), None);
+        c.add(1323123213123);
+        c.add(3312312345324423);
+        assert_eq!(c.get(), Some(1323123213123));
+        assert_eq!(c.get(), Some(3312312345324423));
+        c.add(0);
+        assert_eq!(c.get(), Some(0));
+        let mut read = super::Arithmetic::from_bits(c.to_bits());
+        read.add(5);
+        let all: Vec<u64> = std::iter::from_fn(|| read.get()).collect();
+        assert_eq!(all, [7, 15, 31, 1323123213123, 3312312345324423, 0, 5]);
+    }
+
+    #[test]
+    fn arithmetic_adapts_test() {
+        let mut c = super::Arithmetic::new();
+        for i in 0..10000_u64 {
+            c.add(256 + i % 7);
+        }
+        c.finish();
+        // Every number has 9 bit length, so only the low bits should remain
+        assert!(c.len() < 10000 * 9);
+        for i in 0..10000_u64 {
+            assert_eq!(c.get(), Some(256 + i % 7));
+        }
+        assert_eq!(c.get(), None);
+    }
+
+    #[test]
+    fn arithmetic_boundary_test() {
+        let mut c = super::Arithmetic::new();
+        for n in boundary_values() {
+            c.add(n);
+        }
+        c.finish();
+        for n in boundary_values() {
+            assert_eq!(c.get(), Some(n));
+        }
+        assert_eq!(c.get(), None);
+    }
+}
@@ -0,0 +1,507 @@
+use crate::bits::{Bits, BitOrder};
+use std::io;
+use std::io::{Read, Write};
+
+/**
+    Source of bits, `None` marks end of data
+*/
+pub trait BitRead {
+    fn read_bit(&mut self) -> Option<bool>;
+
+    /**
+        Read `n` bits, most significant first
+    */
+    fn read_bits(&mut self, n: u32) -> Option<u64> {
+        let mut res = 0;
+        for _ in 0..n {
+            res = (res << 1) | self.read_bit()? as u64;
+        }
+        Some(res)
+    }
+
+    /**
+        Look at next `n` bits (at most 56) without consuming them,
+        `None` when less bits are left or source can not look ahead
+    */
+    fn peek_bits(&mut self, _n: u32) -> Option<u64> {
+        None
+    }
+
+    /**
+        Count zeros before the next one, consuming both
+    */
+    fn read_unary(&mut self) -> Option<u32> {
+        let mut res = 0;
+        while !self.read_bit()? {
+            res += 1;
+        }
+        Some(res)
+    }
+}
+
+/**
+    Sink of bits, writing can fail for sinks backed by files or sockets
+*/
+pub trait BitWrite {
+    fn write_bit(&mut self, bit: bool) -> io::Result<()>;
+
+    /**
+        Write `n` lowest bits of value, most significant first
+    */
+    fn write_bits(&mut self, value: u64, n: u32) -> io::Result<()> {
+        for i in (0..n).rev() {
+            self.write_bit(value >> i & 1 == 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl BitWrite for Bits {
+    fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        self.push(bit);
+        Ok(())
+    }
+
+    fn write_bits(&mut self, value: u64, n: u32) -> io::Result<()> {
+        self.push_bits(value, n);
+        Ok(())
+    }
+}
+
+/**
+    Reads `Bits` from given position
+*/
+#[derive(Debug)]
+pub struct BitsCursor<'a> {
+    data: &'a Bits,
+    pub index: usize,
+}
+
+impl<'a> BitsCursor<'a> {
+    pub fn new(data: &'a Bits, index: usize) -> Self {
+        Self {
+            data,
+            index,
+        }
+    }
+}
+
+impl BitRead for BitsCursor<'_> {
+    fn read_bit(&mut self) -> Option<bool> {
+        let res = self.data.get(self.index)?;
+        self.index += 1;
+        Some(res)
+    }
+
+    fn read_bits(&mut self, n: u32) -> Option<u64> {
+        if self.index.checked_add(n as usize)? > self.data.len() {
+            return None;
+        }
+        // Bits shifted out of 64-bit result are skipped, like in the default method
+        self.index += n.saturating_sub(64) as usize;
+        let res = match n {
+            0 => 0,
+            _ => self.data.get_word(self.index) >> (64 - n.min(64)),
+        };
+        self.index += n.min(64) as usize;
+        Some(res)
+    }
+
+    fn peek_bits(&mut self, n: u32) -> Option<u64> {
+        if self.index.checked_add(n as usize)? > self.data.len() {
+            return None;
+        }
+        match n {
+            0 => Some(0),
+            _ => Some(self.data.get_word(self.index) >> (64 - n.min(64))),
+        }
+    }
+
+    fn read_unary(&mut self) -> Option<u32> {
+        let mut res = 0_u32;
+        loop {
+            let left = self.data.len().checked_sub(self.index).filter(|l| *l > 0)?;
+            let zeros = self.data.get_word(self.index).leading_zeros() as usize;
+            if zeros < left.min(64) {
+                self.index += zeros + 1;
+                return res.checked_add(zeros as u32);
+            }
+            self.index += left.min(64);
+            res = res.checked_add(left.min(64) as u32)?;
+        }
+    }
+}
+
+/**
+    Reads bits from any `io::Read` (including `&[u8]`) through 64-bit buffer.
+    Bits are kept in the upper part of the buffer, everything below them is zero.
+    Reading error is treated as the end of data and kept in `error`.
+*/
+#[derive(Debug)]
+pub struct BitReader<R: Read> {
+    inner: R,
+    bytes: Vec<u8>,
+    byte_index: usize,
+    buffer: u64,
+    count: u32,
+    remaining: u64,
+    position: u64,
+    order: BitOrder,
+    pub error: Option<io::Error>,
+}
+
+impl<R: Read> BitReader<R> {
+    const BYTES: usize = 4096;
+
+    pub fn new(inner: R) -> Self {
+        Self::with_limit(inner, u64::MAX)
+    }
+
+    /**
+        Reader which stops after `bits` bits, so padding of last byte is never read
+    */
+    pub fn with_limit(inner: R, bits: u64) -> Self {
+        Self {
+            inner,
+            bytes: Vec::new(),
+            byte_index: 0,
+            buffer: 0,
+            count: 0,
+            remaining: bits,
+            position: 0,
+            order: BitOrder::MsbFirst,
+            error: None,
+        }
+    }
+
+    pub fn with_order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /**
+        Number of bits already consumed
+    */
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn fill_bytes(&mut self) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        self.bytes.resize(Self::BYTES, 0);
+        loop {
+            match self.inner.read(&mut self.bytes) {
+                Ok(n) => {
+                    self.bytes.truncate(n);
+                    self.byte_index = 0;
+                    return n > 0;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.bytes.clear();
+                    self.error = Some(e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            if self.byte_index == self.bytes.len() && !self.fill_bytes() {
+                return;
+            }
+            let byte = match self.order {
+                BitOrder::MsbFirst => self.bytes[self.byte_index],
+                BitOrder::LsbFirst => self.bytes[self.byte_index].reverse_bits(),
+            };
+            self.buffer |= (byte as u64) << (56 - self.count);
+            self.byte_index += 1;
+            self.count += 8;
+        }
+    }
+
+    fn available(&self) -> u32 {
+        (self.count as u64).min(self.remaining) as u32
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.buffer = if n == 64 { 0 } else { self.buffer << n };
+        self.count -= n;
+        self.remaining -= n as u64;
+        self.position += n as u64;
+    }
+}
+
+impl<R: Read> BitRead for BitReader<R> {
+    fn read_bit(&mut self) -> Option<bool> {
+        self.read_bits(1).map(|b| b == 1)
+    }
+
+    fn read_bits(&mut self, n: u32) -> Option<u64> {
+        if n > 32 {
+            let high = self.read_bits(n - 32)?;
+            return Some(high << 32 | self.read_bits(32)?);
+        }
+        let res = self.peek_bits(n)?;
+        self.consume(n);
+        Some(res)
+    }
+
+    fn peek_bits(&mut self, n: u32) -> Option<u64> {
+        if n == 0 {
+            return Some(0);
+        }
+        if self.available() < n {
+            self.refill();
+            if self.available() < n {
+                return None;
+            }
+        }
+        Some(self.buffer >> (64 - n))
+    }
+
+    fn read_unary(&mut self) -> Option<u32> {
+        let mut res = 0;
+        loop {
+            if self.available() == 0 {
+                self.refill();
+            }
+            let available = self.available();
+            if available == 0 {
+                return None;
+            }
+            let zeros = self.buffer.leading_zeros();
+            if zeros < available {
+                self.consume(zeros + 1);
+                return Some(res + zeros);
+            }
+            res += available;
+            self.consume(available);
+        }
+    }
+}
+
+/**
+    Writes bits into any `io::Write` (including `Vec<u8>`) through 64-bit buffer.
+    Last byte is padded with zeros by `finish`, which has to be called at the end.
+*/
+#[derive(Debug)]
+pub struct BitWriter<W: Write> {
+    inner: W,
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+    position: u64,
+    order: BitOrder,
+}
+
+impl<W: Write> BitWriter<W> {
+    const BYTES: usize = 4096;
+
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            bytes: Vec::with_capacity(Self::BYTES),
+            buffer: 0,
+            count: 0,
+            position: 0,
+            order: BitOrder::MsbFirst,
+        }
+    }
+
+    pub fn with_order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.bytes.push(match self.order {
+            BitOrder::MsbFirst => byte,
+            BitOrder::LsbFirst => byte.reverse_bits(),
+        });
+    }
+
+    /**
+        Number of bits written so far
+    */
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn flush_bytes(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.bytes)?;
+        self.bytes.clear();
+        Ok(())
+    }
+
+    /**
+        Pad last byte with zeros and write everything into the inner writer
+    */
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.count > 0 {
+            let padding = 8 - self.count % 8;
+            if padding < 8 {
+                self.buffer <<= padding;
+                self.count += padding;
+            }
+            for i in (0..self.count / 8).rev() {
+                self.push_byte((self.buffer >> (8 * i)) as u8);
+            }
+        }
+        self.flush_bytes()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> BitWrite for BitWriter<W> {
+    fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        self.write_bits(bit as u64, 1)
+    }
+
+    fn write_bits(&mut self, value: u64, n: u32) -> io::Result<()> {
+        if n > 32 {
+            self.write_bits(value >> 32, n - 32)?;
+            return self.write_bits(value, 32);
+        }
+        if n == 0 {
+            return Ok(());
+        }
+        // At most 7 bits wait in buffer, so 32 more always fit
+        self.buffer = (self.buffer << n) | (value & ((1 << n) - 1));
+        self.count += n;
+        self.position += n as u64;
+        while self.count >= 8 {
+            self.count -= 8;
+            self.push_byte((self.buffer >> self.count) as u8);
+        }
+        if self.bytes.len() >= Self::BYTES {
+            self.flush_bytes()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod bit_io_test {
+    use crate::bits::{Bits, BitOrder};
+    use crate::bit_io::{BitsCursor, BitRead, BitWrite, BitReader, BitWriter};
+    use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+    use crate::elias_gamma::GammaCode;
+    use crate::elias_delta::DeltaCode;
+    use crate::elias_omega::OmegaCode;
+    use crate::fibonacci::FibonacciCode;
+
+    fn round_trip<X: UniversalEncoder + UniversalDecoder>(code: X) {
+        let numbers = [0, 1, 2, 7, 255, 256, 1323123213123, 3312312345324423];
+        let mut data = Bits::new();
+        for n in numbers {
+            code.encode(n, &mut data).unwrap();
+        }
+        let mut cursor = BitsCursor::new(&data, 0);
+        for n in numbers {
+            assert_eq!(code.decode(&mut cursor), Some(n));
+        }
+        assert_eq!(code.decode(&mut cursor), None);
+        assert_eq!(cursor.read_bit(), None);
+    }
+
+    #[test]
+    fn codes_test() {
+        round_trip(GammaCode);
+        round_trip(DeltaCode);
+        round_trip(OmegaCode);
+        round_trip(FibonacciCode);
+    }
+
+    #[test]
+    fn writer_test() {
+        let mut writer = BitWriter::new(vec![]);
+        let mut data = Bits::new();
+        for i in 0..200_u64 {
+            let n = (i * 7 % 65) as u32;
+            let value = i.wrapping_mul(0x9e3779b97f4a7c15);
+            writer.write_bits(value, n).unwrap();
+            data.push_bits(value, n);
+        }
+        writer.write_bit(true).unwrap();
+        data.push(true);
+        assert_eq!(writer.position(), data.len() as u64);
+        assert_eq!(writer.finish().unwrap(), data.bytes());
+    }
+
+    #[test]
+    fn order_test() {
+        let mut data = Bits::with_order(BitOrder::LsbFirst);
+        let mut writer = BitWriter::new(vec![]).with_order(BitOrder::LsbFirst);
+        for bits in [(1, 1), (0b100, 3), (0b1, 2), (0x1ff, 9)] {
+            data.push_bits(bits.0, bits.1);
+            writer.write_bits(bits.0, bits.1).unwrap();
+        }
+        assert_eq!(data.bytes(), [0b1110_0011, 0b0111_1111]);
+        assert_eq!(writer.finish().unwrap(), data.bytes());
+        assert_eq!(data.to_order(BitOrder::MsbFirst).bytes(), [0b1100_0111, 0b1111_1110]);
+
+        let mut reader = BitReader::new(data.bytes()).with_order(BitOrder::LsbFirst);
+        assert_eq!(reader.read_bits(6), Some(0b110001));
+        assert_eq!(reader.read_bits(9), Some(0x1ff));
+        let copy = Bits::from_bytes_with_order(data.bytes().to_vec(), data.len(), BitOrder::LsbFirst);
+        assert_eq!(copy.get_bits(0, 15), data.get_bits(0, 15));
+    }
+
+    #[test]
+    fn stream_test() {
+        let numbers: Vec<u64> = (0..20000_u64).map(|i| i * i % 100003).collect();
+        let mut writer = BitWriter::new(vec![]);
+        for n in numbers.iter() {
+            DeltaCode.encode(*n, &mut writer).unwrap();
+        }
+        let size = writer.position();
+        let bytes = writer.finish().unwrap();
+        let mut reader = BitReader::with_limit(std::io::Cursor::new(bytes), size);
+        for n in numbers.iter() {
+            assert_eq!(DeltaCode.decode(&mut reader), Some(*n));
+        }
+        assert_eq!(DeltaCode.decode(&mut reader), None::<u64>);
+    }
+
+    #[test]
+    fn reader_test() {
+        let mut data = Bits::new();
+        data.push_bits(0b1010, 4);
+        data.push_bits(0, 64);
+        data.push_bits(0, 6);
+        data.push(true);
+        data.push_bits(0xdead_beef_cafe, 48);
+        data.push_bits(0b001, 3);
+        let mut reader = BitReader::with_limit(data.bytes(), data.len() as u64);
+        assert_eq!(reader.peek_bits(4), Some(0b1010));
+        assert_eq!(reader.read_bits(2), Some(0b10));
+        assert_eq!(reader.read_bit(), Some(true));
+        assert_eq!(reader.read_unary(), Some(71));
+        assert_eq!(reader.read_bits(48), Some(0xdead_beef_cafe));
+        assert_eq!(reader.position(), 4 + 70 + 1 + 48);
+        assert_eq!(reader.peek_bits(4), None);
+        assert_eq!(reader.read_unary(), Some(2));
+        assert_eq!(reader.read_bit(), None);
+
+        for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let data = data.to_order(order);
+            let mut cursor = BitsCursor::new(&data, 0);
+            assert_eq!(cursor.peek_bits(4), Some(0b1010));
+            assert_eq!(cursor.read_bits(3), Some(0b101));
+            assert_eq!(cursor.read_unary(), Some(71));
+            assert_eq!(cursor.read_bits(48), Some(0xdead_beef_cafe));
+            assert_eq!(cursor.peek_bits(4), None);
+            assert_eq!(cursor.read_bits(3), Some(0b001));
+            assert_eq!(cursor.read_unary(), None);
+        }
+    }
+}
@@ -3,26 +3,106 @@ use std::fs::File;
 use std::io::{Write, Read};
 use std::path::Path;
 
-#[derive(Debug)]
+/**
+    Packing of bits into bytes, MSB first is used unless stated otherwise.
+    GIF, LZC and DEFLATE fill bytes starting from the least significant bit.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder{
+    #[default]
+    MsbFirst,
+    LsbFirst
+}
+
+#[derive(Debug, Clone)]
 pub struct Bits{
     data: Vec<u8>,
-    size: usize
+    size: usize,
+    order: BitOrder
+}
+
+impl Default for Bits{
+    fn default() -> Self{
+        Self::new()
+    }
 }
 
 impl Bits{
     const BIN: [u8; 8] = [128, 64, 32, 16, 8, 4, 2, 1];
 
     pub fn new() -> Self{
+        Self::with_order(BitOrder::MsbFirst)
+    }
+
+    pub fn with_order(order: BitOrder) -> Self{
         Self{
             data: Vec::new(),
-            size: 0
+            size: 0,
+            order
+        }
+    }
+
+    /**
+        Wrap bytes holding `size` bits, any bits after `size` are ignored
+    */
+    pub fn from_bytes(data: Vec<u8>, size: usize) -> Self{
+        Self::from_bytes_with_order(data, size, BitOrder::MsbFirst)
+    }
+
+    pub fn from_bytes_with_order(mut data: Vec<u8>, size: usize, order: BitOrder) -> Self{
+        let size = size.min(data.len() * 8);
+        data.truncate(size.div_ceil(8));
+        if !size.is_multiple_of(8) {
+            // Clear unused bits, so later push can set them
+            data[size / 8] &= match order {
+                BitOrder::MsbFirst => !(0xff >> (size % 8)),
+                BitOrder::LsbFirst => !(0xff << (size % 8))
+            };
+        }
+        Self{
+            data,
+            size,
+            order
+        }
+    }
+
+    pub fn order(&self) -> BitOrder{
+        self.order
+    }
+
+    /**
+        Same bits packed in given order
+    */
+    pub fn to_order(&self, order: BitOrder) -> Self{
+        let mut res = self.clone();
+        if order != self.order {
+            for byte in res.data.iter_mut() {
+                *byte = byte.reverse_bits();
+            }
+            res.order = order;
         }
+        res
+    }
+
+    fn mask(&self, i: usize) -> u8{
+        match self.order {
+            BitOrder::MsbFirst => Self::BIN[i % 8],
+            BitOrder::LsbFirst => 1 << (i % 8)
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8]{
+        &self.data
     }
 
     pub fn len(&self) -> usize{
         self.size
     }
 
+    pub fn is_empty(&self) -> bool{
+        self.size == 0
+    }
+
     pub fn push(&mut self, c: bool) {
         let sector = self.size % 8;
         if sector == 0 {
@@ -30,7 +110,7 @@ impl Bits{
         }
         if c {
             let block = self.size / 8;
-            self.data[block] |= Self::BIN[sector]
+            self.data[block] |= self.mask(self.size)
         }
         self.size += 1;
     }
@@ -40,11 +120,146 @@ impl Bits{
     */
     pub fn get(&self, i: usize) -> Option<bool> {
         if i < self.size{
-            return Some(!self.data[i / 8] & Self::BIN[i % 8] == 0);
+            return Some(!self.data[i / 8] & self.mask(i) == 0);
         }
         None
     }
 
+    /**
+        Push `n` lowest bits of value, most significant first
+    */
+    pub fn push_bits(&mut self, value: u64, n: u32) {
+        for i in (0..n).rev() {
+            self.push(value >> i & 1 == 1);
+        }
+    }
+
+    /**
+        Read `n` bits starting from position `i`, most significant first
+    */
+    pub fn get_bits(&self, i: usize, n: u32) -> Option<u64> {
+        let end = i + n as usize;
+        if end > self.size {
+            return None;
+        }
+        let mut res = 0;
+        if self.order == BitOrder::LsbFirst {
+            for j in i..end {
+                res = (res << 1) | self.get(j)? as u64;
+            }
+            return Some(res);
+        }
+        // Whole parts of bytes at once
+        let mut j = i;
+        while j < end {
+            let offset = j % 8;
+            let take = (8 - offset).min(end - j);
+            let byte = (self.data[j / 8] >> (8 - offset - take)) as u64;
+            res = (res << take) | (byte & ((1 << take) - 1));
+            j += take;
+        }
+        Some(res)
+    }
+
+    /**
+        64 bits starting from position `i` with the first one as the most significant,
+        bits past the end read as zeros
+    */
+    pub fn get_word(&self, i: usize) -> u64 {
+        let first = i / 8;
+        let mut bytes = [0_u8; 8];
+        let mut next = 0_u8;
+        match self.data.get(first..first + 9) {
+            Some(d) => {
+                bytes.copy_from_slice(&d[..8]);
+                next = d[8];
+            }
+            None => {
+                let tail = self.data.iter().skip(first);
+                for (b, d) in bytes.iter_mut().chain(std::iter::once(&mut next)).zip(tail) {
+                    *b = *d;
+                }
+            }
+        }
+        let mut word = u64::from_be_bytes(bytes);
+        if self.order == BitOrder::LsbFirst {
+            // Reverse bits inside every byte
+            word = word.reverse_bits().swap_bytes();
+            next = next.reverse_bits();
+        }
+        match i % 8 {
+            0 => word,
+            offset => word << offset | (next >> (8 - offset)) as u64,
+        }
+    }
+
+    /**
+        Bits from `start` to `end` as a new buffer
+    */
+    pub fn slice(&self, start: usize, end: usize) -> Option<Self>{
+        let mut res = Self::with_order(self.order);
+        let mut i = start;
+        while i < end {
+            let n = (end - i).min(64) as u32;
+            res.push_bits(self.get_bits(i, n)?, n);
+            i += n as usize;
+        }
+        Some(res)
+    }
+
+    /**
+        Push all bits of other buffer
+    */
+    pub fn append(&mut self, other: &Self){
+        if self.order != other.order {
+            self.append(&other.to_order(self.order));
+            return;
+        }
+        let shift = self.size % 8;
+        if shift == 0 {
+            self.data.extend_from_slice(&other.data);
+        } else {
+            // Every byte of other is split between the last byte and a new one
+            self.data.reserve(other.data.len());
+            for byte in other.data.iter() {
+                let last = self.data.len() - 1;
+                let (low, high) = match self.order {
+                    BitOrder::MsbFirst => (byte >> shift, byte << (8 - shift)),
+                    BitOrder::LsbFirst => (byte << shift, byte >> (8 - shift)),
+                };
+                self.data[last] |= low;
+                self.data.push(high);
+            }
+        }
+        self.size += other.size;
+        self.data.truncate(self.size.div_ceil(8));
+    }
+
+    /**
+        Push positive number in Elias gamma code, used for headers and tables
+    */
+    pub fn push_gamma(&mut self, n: u64) {
+        let size = 64 - n.leading_zeros();
+        for _ in 1..size {
+            self.push(false);
+        }
+        self.push_bits(n, size);
+    }
+
+    /**
+        Read number in Elias gamma code at position `i`, moving `i` past it
+    */
+    pub fn get_gamma(&self, i: &mut usize) -> Option<u64> {
+        let mut size = 1;
+        while !self.get(*i)? {
+            *i += 1;
+            size += 1;
+        }
+        let res = self.get_bits(*i, size)?;
+        *i += size as usize;
+        Some(res)
+    }
+
     pub fn save_to_file<X>(&self, path: X) -> Result<(), String> where X: AsRef<Path> {
         let mut file;
         match File::create(path){
@@ -52,14 +267,12 @@ impl Bits{
             Err(_e) => return Err("Unable to open file".parse().unwrap())
         }
         //Save data
-        match file.write(self.data.as_ref()){
-            Err(_e) => return Err("Unable to save file".parse().unwrap()),
-            _ => {}
+        if let Err(_e) = file.write(self.data.as_ref()){
+            return Err("Unable to save file".parse().unwrap());
         }
         //Save number of encoded characters
-        match file.sync_all(){
-            Err(_e) => return Err("Unable to save file".parse().unwrap()),
-            _ => {}
+        if let Err(_e) = file.sync_all(){
+            return Err("Unable to save file".parse().unwrap());
         }
         Ok(())
     }
@@ -71,13 +284,13 @@ impl Bits{
             Err(_e) => return Err("Unable to open file".parse().unwrap())
         }
         let mut data = vec![];
-        match file.read_to_end(data.as_mut()) {
-            Err(_e) => return Err("Unable to read file".parse().unwrap()),
-            _ => {}
+        if let Err(_e) = file.read_to_end(data.as_mut()) {
+            return Err("Unable to read file".parse().unwrap());
         }
         Ok(Self {
             size: (data.len() * 8),
             data,
+            order: BitOrder::MsbFirst,
         })
     }
 
@@ -86,15 +299,41 @@ impl Bits{
     }
 }
 
-pub fn entropy(data: &Vec<u8>) -> f64{
+pub fn entropy(data: &[u8]) -> f64{
     let mut temp = [0_u64; 256];
     for d in data{
         temp[*d as usize] += 1;
     }
-    let sum = temp.iter().fold(0_u64, |a, b| a+*b as u64);
+    let sum = temp.iter().fold(0_u64, |a, b| a+*b);
     temp.iter().fold(0.0, |acc, x| if *x > 0{
         acc - (*x as f64/ sum as f64) * ((*x) as f64 / sum as f64).log2()
     }  else{
         acc
     })
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod bits_test {
+    use crate::bits::{Bits, BitOrder};
+
+    #[test]
+    fn append_test() {
+        for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            for (a, b) in [(0, 13), (3, 13), (5, 64), (7, 1), (12, 70)] {
+                let mut left = Bits::with_order(order);
+                let mut right = Bits::with_order(BitOrder::MsbFirst);
+                let mut expected = Bits::with_order(order);
+                for i in 0..a {
+                    left.push(i % 3 == 0);
+                    expected.push(i % 3 == 0);
+                }
+                for i in 0..b {
+                    right.push(i % 5 < 2);
+                    expected.push(i % 5 < 2);
+                }
+                left.append(&right);
+                assert_eq!(left.len(), expected.len());
+                assert_eq!(left.bytes(), expected.bytes());
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::bits::Bits;
+use crate::container::CodeType;
+use crate::lzw;
+use std::io::{Read, Seek, SeekFrom};
+use std::thread;
+
+/**
+    Start of one independently compressed block
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockEntry {
+    pub code: CodeType,
+    pub offset: u64,
+    pub bit_offset: u64,
+}
+
+/**
+    File format for random access: magic bytes, version and coded blocks one after another
+    without padding. Every block is LZW compressed with fresh dictionary.
+    Index follows the blocks: for every block coding id (u8), uncompressed offset
+    and compressed bit offset (u64 little endian), then uncompressed size,
+    number of coded bits and number of blocks (u64 little endian).
+*/
+pub const MAGIC: [u8; 4] = *b"LZWB";
+pub const VERSION: u8 = 1;
+const HEADER_SIZE: u64 = 5;
+const ENTRY_SIZE: u64 = 17;
+const TRAILER_SIZE: u64 = 24;
+
+pub fn is_block_file(bytes: &[u8]) -> bool {
+    bytes.len() > MAGIC.len() && bytes[..MAGIC.len()] == MAGIC
+}
+
+/**
+    Apply `f` to every item using up to `threads` threads, results keep order of items
+*/
+fn parallel_map<T: Sync, U: Send, F: Fn(&T) -> U + Sync>(items: &[T], threads: usize, f: F) -> Vec<U> {
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let per_thread = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|s| {
+        let handles: Vec<_> = items.chunks(per_thread)
+            .map(|part| s.spawn(move || part.iter().map(f).collect::<Vec<U>>()))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    })
+}
+
+fn compress_block(data: &[u8], code: Option<CodeType>) -> (CodeType, Bits) {
+    let codes = lzw::encode(data);
+    let (c, coded) = match code {
+        Some(c) => (c, c.encode(&codes)),
+        None => CodeType::best(&codes),
+    };
+    (c, coded.to_bits())
+}
+
+fn decompress_block(code: CodeType, data: Bits) -> Result<Vec<u8>, String> {
+    let mut coder = code.from_bits(data);
+    lzw::decode(std::iter::from_fn(|| coder.get()))
+}
+
+/**
+    Split data into blocks of `block_size` bytes and compress each of them,
+    `None` as coding selects the smallest coding for every block
+*/
+pub fn compress_blocks(data: &[u8], code: Option<CodeType>, block_size: usize) -> Vec<u8> {
+    compress_blocks_parallel(data, code, block_size, 1)
+}
+
+/**
+    Same as `compress_blocks`, blocks are compressed by `threads` threads
+*/
+pub fn compress_blocks_parallel(data: &[u8], code: Option<CodeType>, block_size: usize, threads: usize) -> Vec<u8> {
+    let block_size = block_size.max(1);
+    let chunks: Vec<&[u8]> = data.chunks(block_size).collect();
+    let compressed = parallel_map(&chunks, threads, |chunk| compress_block(chunk, code));
+    let mut body = Bits::new();
+    let mut index = vec![];
+    for (i, (c, bits)) in compressed.iter().enumerate() {
+        index.push(BlockEntry {
+            code: *c,
+            offset: (i * block_size) as u64,
+            bit_offset: body.len() as u64,
+        });
+        body.append(bits);
+    }
+    let mut res = MAGIC.to_vec();
+    res.push(VERSION);
+    res.extend_from_slice(body.bytes());
+    for entry in index.iter() {
+        res.push(entry.code.id());
+        res.extend_from_slice(&entry.offset.to_le_bytes());
+        res.extend_from_slice(&entry.bit_offset.to_le_bytes());
+    }
+    res.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    res.extend_from_slice(&(body.len() as u64).to_le_bytes());
+    res.extend_from_slice(&(index.len() as u64).to_le_bytes());
+    res
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut res = [0_u8; 8];
+    res.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(res)
+}
+
+/**
+    Reads only the index and blocks which are asked for
+*/
+#[derive(Debug)]
+pub struct BlockReader<R> {
+    /**
+        Number of threads decoding blocks of a range
+    */
+    pub threads: usize,
+    inner: R,
+    index: Vec<BlockEntry>,
+    size: u64,
+    bits: u64,
+}
+
+impl<R: Read + Seek> BlockReader<R> {
+    pub fn new(mut inner: R) -> Result<Self, String> {
+        let io_error = |e: std::io::Error| e.to_string();
+        let file_size = inner.seek(SeekFrom::End(0)).map_err(io_error)?;
+        if file_size < HEADER_SIZE + TRAILER_SIZE {
+            return Err("Truncated block file".to_string());
+        }
+        let mut header = [0_u8; HEADER_SIZE as usize];
+        inner.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        inner.read_exact(&mut header).map_err(io_error)?;
+        if !is_block_file(&header) {
+            return Err("Missing block file header".to_string());
+        }
+        if header[MAGIC.len()] != VERSION {
+            return Err(format!("Unsupported block file version {}", header[MAGIC.len()]));
+        }
+        let mut trailer = [0_u8; TRAILER_SIZE as usize];
+        inner.seek(SeekFrom::End(-(TRAILER_SIZE as i64))).map_err(io_error)?;
+        inner.read_exact(&mut trailer).map_err(io_error)?;
+        let size = read_u64(&trailer);
+        let bits = read_u64(&trailer[8..]);
+        let count = read_u64(&trailer[16..]);
+        let expected = count.checked_mul(ENTRY_SIZE)
+            .and_then(|s| s.checked_add(bits.div_ceil(8)))
+            .and_then(|s| s.checked_add(HEADER_SIZE + TRAILER_SIZE));
+        if expected != Some(file_size) {
+            return Err("Damaged block index".to_string());
+        }
+        let mut entries = vec![0_u8; (count * ENTRY_SIZE) as usize];
+        inner.seek(SeekFrom::Start(HEADER_SIZE + bits.div_ceil(8))).map_err(io_error)?;
+        inner.read_exact(&mut entries).map_err(io_error)?;
+        let mut index = vec![];
+        for entry in entries.chunks(ENTRY_SIZE as usize) {
+            let code = match CodeType::from_id(entry[0]) {
+                Some(c) => c,
+                None => return Err(format!("Unknown coding {}", entry[0])),
+            };
+            index.push(BlockEntry {
+                code,
+                offset: read_u64(&entry[1..]),
+                bit_offset: read_u64(&entry[9..]),
+            });
+        }
+        // Blocks cover the data from the start, each one is non empty and inside the file
+        let first = index.first().map(|e| e.offset == 0).unwrap_or(size == 0);
+        let inside = index.iter().all(|e| e.offset < size && e.bit_offset <= bits);
+        let ordered = index.windows(2).all(|w| w[0].offset < w[1].offset && w[0].bit_offset <= w[1].bit_offset);
+        if !first || !inside || !ordered {
+            return Err("Damaged block index".to_string());
+        }
+        Ok(Self {
+            threads: 1,
+            inner,
+            index,
+            size,
+            bits,
+        })
+    }
+
+    /**
+        Size of uncompressed data
+    */
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn index(&self) -> &[BlockEntry] {
+        &self.index
+    }
+
+    /**
+        Coded bits of a block
+    */
+    fn read_block_bits(&mut self, i: usize) -> Result<(CodeType, Bits), String> {
+        let entry = match self.index.get(i) {
+            Some(e) => *e,
+            None => return Err(format!("Missing block {}", i)),
+        };
+        let end = self.index.get(i + 1).map(|e| e.bit_offset).unwrap_or(self.bits);
+        if entry.bit_offset > end || end > self.bits {
+            return Err("Damaged block index".to_string());
+        }
+        let first = entry.bit_offset / 8;
+        let mut bytes = vec![0_u8; (end.div_ceil(8) - first) as usize];
+        self.inner.seek(SeekFrom::Start(HEADER_SIZE + first)).map_err(|e| e.to_string())?;
+        self.inner.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+        let skip = (entry.bit_offset % 8) as usize;
+        let data = Bits::from_bytes(bytes, skip + (end - entry.bit_offset) as usize)
+            .slice(skip, skip + (end - entry.bit_offset) as usize)
+            .unwrap();
+        Ok((entry.code, data))
+    }
+
+    pub fn read_block(&mut self, i: usize) -> Result<Vec<u8>, String> {
+        let (code, data) = self.read_block_bits(i)?;
+        decompress_block(code, data)
+    }
+
+    /**
+        `len` bytes starting from `start`, decoding only blocks which contain them
+    */
+    pub fn read_range(&mut self, start: u64, len: u64) -> Result<Vec<u8>, String> {
+        let end = start.saturating_add(len).min(self.size);
+        let mut res = vec![];
+        if start >= end {
+            return Ok(res);
+        }
+        let first = self.index.partition_point(|e| e.offset <= start).saturating_sub(1);
+        let last = self.index.partition_point(|e| e.offset < end);
+        // Reading is sequential, only decoding runs in parallel
+        let mut coded = vec![];
+        for i in first..last {
+            coded.push(self.read_block_bits(i)?);
+        }
+        let decoded = parallel_map(&coded, self.threads, |(code, data)| decompress_block(*code, data.clone()));
+        for (i, block) in (first..last).zip(decoded) {
+            let block = block?;
+            let offset = self.index[i].offset;
+            let from = start.saturating_sub(offset) as usize;
+            let to = ((end - offset) as usize).min(block.len());
+            if from < to {
+                res.extend_from_slice(&block[from..to]);
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn read_all(&mut self) -> Result<Vec<u8>, String> {
+        self.read_range(0, self.size)
+    }
+}
+
+#[cfg(test)]
+mod blocks_test {
+    use super::{compress_blocks, compress_blocks_parallel, BlockReader, ENTRY_SIZE, TRAILER_SIZE};
+    use crate::container::CodeType;
+    use std::io::Cursor;
+
+    #[test]
+    fn blocks_test() {
+        let data: Vec<u8> = (0..20000_u32).map(|i| (i * i % 251) as u8 ^ (i / 300) as u8).collect();
+        for code in [Some(CodeType::Fibonacci), Some(CodeType::Arithmetic), None] {
+            let file = compress_blocks(&data, code, 3000);
+            let mut reader = BlockReader::new(Cursor::new(file)).unwrap();
+            assert_eq!(reader.size(), data.len() as u64);
+            assert_eq!(reader.index().len(), 7);
+            assert_eq!(reader.read_all().unwrap(), data);
+            for (start, len) in [(0, 10), (2990, 20), (5999, 1), (6000, 6001), (19990, 100), (20000, 5)] {
+                let end = (start + len).min(data.len());
+                assert_eq!(reader.read_range(start as u64, len as u64).unwrap(), data[start..end]);
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_test() {
+        let mut file = compress_blocks(b"abcabcabcabc", Some(CodeType::Gamma), 4);
+        assert!(BlockReader::new(Cursor::new(file[..file.len() - 1].to_vec())).is_err());
+        // Last of three blocks starting at the same offset as the second one
+        let mut swapped = file.clone();
+        let last = file.len() - (TRAILER_SIZE + ENTRY_SIZE) as usize;
+        swapped[last + 1..last + 9].copy_from_slice(&4_u64.to_le_bytes());
+        assert_eq!(BlockReader::new(Cursor::new(swapped)).unwrap_err(), "Damaged block index");
+        file.push(0);
+        assert!(BlockReader::new(Cursor::new(file)).is_err());
+        let empty = compress_blocks(b"", Some(CodeType::Gamma), 4);
+        assert_eq!(BlockReader::new(Cursor::new(empty)).unwrap().read_all(), Ok(vec![]));
+    }
+
+    #[test]
+    fn threads_test() {
+        let data: Vec<u8> = (0..30000_u32).map(|i| (i % 97) as u8 ^ (i / 1000) as u8).collect();
+        let file = compress_blocks_parallel(&data, Some(CodeType::Delta), 2000, 4);
+        assert_eq!(file, compress_blocks(&data, Some(CodeType::Delta), 2000));
+        let mut reader = BlockReader::new(Cursor::new(file)).unwrap();
+        reader.threads = 3;
+        assert_eq!(reader.read_all().unwrap(), data);
+        assert_eq!(reader.read_range(1999, 4002).unwrap(), data[1999..6001]);
+    }
+}
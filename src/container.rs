@@ -0,0 +1,438 @@
+use crate::algorithm::Algorithm;
+use crate::bits::{Bits, BitOrder};
+use crate::universal_coding::{UniversalCode, Creatable, CodewordLength};
+use crate::elias_gamma::EliasGamma;
+use crate::elias_delta::EliasDelta;
+use crate::elias_omega::EliasOmega;
+use crate::fibonacci::Fibonacci;
+use crate::arithmetic::Arithmetic;
+use crate::rans::Rans;
+use crate::huffman::Huffman;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/**
+    Every back end which can code LZW indices, stored in file as one byte id
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeType {
+    Gamma,
+    Delta,
+    Omega,
+    Fibonacci,
+    Arithmetic,
+    Rans,
+    Huffman,
+}
+
+impl CodeType {
+    pub const ALL: [CodeType; 7] = [
+        CodeType::Gamma,
+        CodeType::Delta,
+        CodeType::Omega,
+        CodeType::Fibonacci,
+        CodeType::Arithmetic,
+        CodeType::Rans,
+        CodeType::Huffman,
+    ];
+
+    pub fn id(self) -> u8 {
+        match self {
+            CodeType::Gamma => 0,
+            CodeType::Delta => 1,
+            CodeType::Omega => 2,
+            CodeType::Fibonacci => 3,
+            CodeType::Arithmetic => 4,
+            CodeType::Rans => 5,
+            CodeType::Huffman => 6,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CodeType::Gamma => "gamma",
+            CodeType::Delta => "delta",
+            CodeType::Omega => "omega",
+            CodeType::Fibonacci => "fibonacci",
+            CodeType::Arithmetic => "arithmetic",
+            CodeType::Rans => "rans",
+            CodeType::Huffman => "huffman",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.name() == name)
+    }
+
+    fn boxed<X: UniversalCode + Creatable + 'static>(data: Bits) -> Box<dyn UniversalCode> {
+        Box::new(X::from_bits(data))
+    }
+
+    /**
+        Coder reading given stream
+    */
+    pub fn from_bits(self, data: Bits) -> Box<dyn UniversalCode> {
+        match self {
+            CodeType::Gamma => Self::boxed::<EliasGamma>(data),
+            CodeType::Delta => Self::boxed::<EliasDelta>(data),
+            CodeType::Omega => Self::boxed::<EliasOmega>(data),
+            CodeType::Fibonacci => Self::boxed::<Fibonacci>(data),
+            CodeType::Arithmetic => Self::boxed::<Arithmetic>(data),
+            CodeType::Rans => Self::boxed::<Rans>(data),
+            CodeType::Huffman => Self::boxed::<Huffman>(data),
+        }
+    }
+
+    /**
+        Empty coder ready for `add`
+    */
+    pub fn create(self) -> Box<dyn UniversalCode> {
+        match self {
+            CodeType::Gamma => Box::new(EliasGamma::new()),
+            CodeType::Delta => Box::new(EliasDelta::new()),
+            CodeType::Omega => Box::new(EliasOmega::new()),
+            CodeType::Fibonacci => Box::new(Fibonacci::new()),
+            CodeType::Arithmetic => Box::new(Arithmetic::new()),
+            CodeType::Rans => Box::new(Rans::new()),
+            CodeType::Huffman => Box::new(Huffman::new()),
+        }
+    }
+
+    pub fn encode(self, codes: &[u64]) -> Box<dyn UniversalCode> {
+        let mut res = self.create();
+        res.encode_slice(codes);
+        res.finish();
+        res
+    }
+
+    fn stream_len<X: CodewordLength>(codes: &[u64]) -> usize {
+        codes.iter().map(|c| X::encoded_len(*c) as usize).sum()
+    }
+
+    /**
+        Exact size of coded numbers in bits, for codes which do not need encoding to know it
+    */
+    pub fn estimate(self, codes: &[u64]) -> Option<usize> {
+        match self {
+            CodeType::Gamma => Some(Self::stream_len::<EliasGamma>(codes)),
+            CodeType::Delta => Some(Self::stream_len::<EliasDelta>(codes)),
+            CodeType::Omega => Some(Self::stream_len::<EliasOmega>(codes)),
+            CodeType::Fibonacci => Some(Self::stream_len::<Fibonacci>(codes)),
+            _ => None,
+        }
+    }
+
+    /**
+        Encode numbers with every back end and keep the shortest result,
+        codes with known codeword lengths are only encoded when they win
+    */
+    pub fn best(codes: &[u64]) -> (Self, Box<dyn UniversalCode>) {
+        let mut best = Self::ALL[0];
+        let mut best_size = usize::MAX;
+        let mut best_coded = None;
+        for c in Self::ALL.iter().copied() {
+            let (size, coded) = match c.estimate(codes) {
+                Some(size) => (size, None),
+                None => {
+                    let coded = c.encode(codes);
+                    (coded.len(), Some(coded))
+                }
+            };
+            if size < best_size {
+                best = c;
+                best_size = size;
+                best_coded = coded;
+            }
+        }
+        (best, best_coded.unwrap_or_else(|| best.encode(codes)))
+    }
+}
+
+/**
+    Part of the index stream coded with a single back end
+*/
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub code: CodeType,
+    pub data: Bits,
+}
+
+/**
+    File format: magic bytes, version, flags and sections one after another.
+    Flags hold bit order of all sections in the lowest bit (version 1 has no flags).
+    Second bit of flags marks data coded with preset dictionary, its id (u32 little endian)
+    follows the flags (since version 3).
+    Third bit marks algorithm other than LZW, its id (u8) follows the preset id (since version 4).
+    Flags unknown to the version are rejected.
+    Section is stored as back end id, number of bits (u64 little endian) and the bits.
+    Streams are stored with exact length, so decoders never see padding.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Container {
+    pub sections: Vec<Section>,
+    pub order: BitOrder,
+    /**
+        Id of preset dictionary the LZW coding started from
+    */
+    pub preset: Option<u32>,
+    pub algorithm: Algorithm,
+}
+
+impl Container {
+    pub const MAGIC: [u8; 4] = *b"LZWU";
+    pub const VERSION: u8 = 4;
+    const LSB_FIRST: u8 = 1;
+    const PRESET: u8 = 2;
+    const ALGORITHM: u8 = 4;
+
+    pub fn new() -> Self {
+        Self {
+            sections: Vec::new(),
+            order: BitOrder::MsbFirst,
+            preset: None,
+            algorithm: Algorithm::Lzw,
+        }
+    }
+
+    pub fn push(&mut self, code: CodeType, data: Bits) {
+        self.sections.push(Section { code, data });
+    }
+
+    /**
+        Files written before the container format are plain streams without header
+    */
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.len() > Self::MAGIC.len() && bytes[..Self::MAGIC.len()] == Self::MAGIC
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Self::MAGIC.to_vec();
+        res.push(Self::VERSION);
+        let mut flags = match self.order {
+            BitOrder::MsbFirst => 0,
+            BitOrder::LsbFirst => Self::LSB_FIRST,
+        };
+        if self.preset.is_some() {
+            flags |= Self::PRESET;
+        }
+        if self.algorithm != Algorithm::Lzw {
+            flags |= Self::ALGORITHM;
+        }
+        res.push(flags);
+        if let Some(id) = self.preset {
+            res.extend_from_slice(&id.to_le_bytes());
+        }
+        if self.algorithm != Algorithm::Lzw {
+            res.push(self.algorithm.id());
+        }
+        for section in self.sections.iter() {
+            res.push(section.code.id());
+            res.extend_from_slice(&(section.data.len() as u64).to_le_bytes());
+            res.extend_from_slice(section.data.to_order(self.order).bytes());
+        }
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !Self::is_container(bytes) {
+            return Err("Missing container header".to_string());
+        }
+        let mut position = Self::MAGIC.len();
+        let mut res = Self::new();
+        match bytes[position] {
+            1 => position += 1,
+            version @ 2..=4 => {
+                if position + 1 >= bytes.len() {
+                    return Err("Truncated container header".to_string());
+                }
+                let flags = bytes[position + 1];
+                let known = match version {
+                    2 => Self::LSB_FIRST,
+                    3 => Self::LSB_FIRST | Self::PRESET,
+                    _ => Self::LSB_FIRST | Self::PRESET | Self::ALGORITHM,
+                };
+                if flags & !known != 0 {
+                    return Err(format!("Unknown container flags {}", flags & !known));
+                }
+                if flags & Self::LSB_FIRST != 0 {
+                    res.order = BitOrder::LsbFirst;
+                }
+                position += 2;
+                if flags & Self::PRESET != 0 {
+                    if position + 4 > bytes.len() {
+                        return Err("Truncated container header".to_string());
+                    }
+                    let mut id = [0_u8; 4];
+                    id.copy_from_slice(&bytes[position..position + 4]);
+                    res.preset = Some(u32::from_le_bytes(id));
+                    position += 4;
+                }
+                if flags & Self::ALGORITHM != 0 {
+                    res.algorithm = match bytes.get(position).map(|id| Algorithm::from_id(*id)) {
+                        Some(Some(a)) => a,
+                        Some(None) => return Err(format!("Unknown algorithm {}", bytes[position])),
+                        None => return Err("Truncated container header".to_string()),
+                    };
+                    position += 1;
+                }
+            }
+            v => return Err(format!("Unsupported container version {}", v)),
+        }
+        while position < bytes.len() {
+            if position + 9 > bytes.len() {
+                return Err("Truncated section header".to_string());
+            }
+            let code = match CodeType::from_id(bytes[position]) {
+                Some(c) => c,
+                None => return Err(format!("Unknown coding {}", bytes[position])),
+            };
+            let mut size = [0_u8; 8];
+            size.copy_from_slice(&bytes[position + 1..position + 9]);
+            let size = u64::from_le_bytes(size) as usize;
+            position += 9;
+            let end = position + size.div_ceil(8);
+            if end > bytes.len() {
+                return Err("Truncated section".to_string());
+            }
+            res.push(code, Bits::from_bytes_with_order(bytes[position..end].to_vec(), size, res.order));
+            position = end;
+        }
+        Ok(res)
+    }
+
+    pub fn save_to_file<X>(&self, path: X) -> Result<(), String> where X: AsRef<Path> {
+        let mut file = match File::create(path) {
+            Ok(f) => f,
+            Err(_e) => return Err("Unable to open file".to_string()),
+        };
+        if let Err(_e) = file.write_all(&self.to_bytes()) {
+            return Err("Unable to save file".to_string());
+        }
+        if let Err(_e) = file.sync_all() {
+            return Err("Unable to save file".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod container_test {
+    use super::{CodeType, Container};
+    use crate::algorithm::Algorithm;
+    use crate::bits::BitOrder;
+
+    #[test]
+    fn container_test() {
+        let codes = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 0, 258, 1000];
+        let mut container = Container::new();
+        for code in CodeType::ALL.iter() {
+            container.push(*code, code.encode(&codes).to_bits());
+        }
+        let read = Container::from_bytes(&container.to_bytes()).unwrap();
+        assert_eq!(read.sections.len(), CodeType::ALL.len());
+        for section in read.sections {
+            let mut coder = section.code.from_bits(section.data);
+            let decoded: Vec<u64> = std::iter::from_fn(|| coder.get()).collect();
+            assert_eq!(decoded, codes);
+        }
+    }
+
+    #[test]
+    fn order_test() {
+        let codes = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 0, 258, 1000];
+        let mut msb = Container::new();
+        let mut lsb = Container::new();
+        lsb.order = BitOrder::LsbFirst;
+        for code in CodeType::ALL.iter() {
+            msb.push(*code, code.encode(&codes).to_bits());
+            lsb.push(*code, code.encode(&codes).to_bits());
+        }
+        assert_ne!(msb.to_bytes(), lsb.to_bytes());
+        let read = Container::from_bytes(&lsb.to_bytes()).unwrap();
+        assert_eq!(read.order, BitOrder::LsbFirst);
+        for section in read.sections {
+            let mut coder = section.code.from_bits(section.data);
+            let decoded: Vec<u64> = std::iter::from_fn(|| coder.get()).collect();
+            assert_eq!(decoded, codes);
+        }
+    }
+
+    #[test]
+    fn estimate_test() {
+        let codes: Vec<u64> = (0..1000).map(|i| i * i).collect();
+        for code in CodeType::ALL.iter() {
+            if let Some(size) = code.estimate(&codes) {
+                assert_eq!(size, code.encode(&codes).len());
+            }
+        }
+    }
+
+    #[test]
+    fn best_test() {
+        let codes: Vec<u64> = (0..1000).map(|i| 300 + i % 3).collect();
+        let (code, coded) = CodeType::best(&codes);
+        for other in CodeType::ALL.iter() {
+            assert!(coded.len() <= other.encode(&codes).len(), "{:?} beaten by {:?}", code, other);
+        }
+    }
+
+    #[test]
+    fn signed_test() {
+        let codes = [0, -1, 1, -1000, 1000, 12345678, -87654321, i64::MAX, i64::MIN];
+        for code in CodeType::ALL.iter() {
+            let mut coder = code.create();
+            for c in codes {
+                coder.add_signed(c);
+            }
+            coder.finish();
+            let mut coder = code.from_bits(coder.to_bits());
+            let decoded: Vec<i64> = std::iter::from_fn(|| coder.get_signed()).collect();
+            assert_eq!(decoded, codes, "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn bulk_test() {
+        let codes: Vec<u64> = (0..5000).map(|i| i * 7919 % 3000).collect();
+        for code in CodeType::ALL.iter() {
+            let mut coder = code.from_bits(code.encode(&codes).to_bits());
+            let mut decoded = vec![];
+            assert_eq!(coder.decode_into(&mut decoded, 1000), 1000);
+            assert_eq!(coder.get(), Some(codes[1000]));
+            assert_eq!(coder.decode_into(&mut decoded, 10000), 3999);
+            assert_eq!(decoded[..1000], codes[..1000]);
+            assert_eq!(decoded[1000..], codes[1001..]);
+        }
+    }
+
+    #[test]
+    fn preset_test() {
+        let mut container = Container::new();
+        container.preset = Some(0xdeadbeef);
+        container.order = BitOrder::LsbFirst;
+        container.push(CodeType::Gamma, CodeType::Gamma.encode(&[1, 2, 3]).to_bits());
+        let read = Container::from_bytes(&container.to_bytes()).unwrap();
+        assert_eq!(read.preset, Some(0xdeadbeef));
+        assert_eq!(read.algorithm, Algorithm::Lzw);
+        assert_eq!(read.order, BitOrder::LsbFirst);
+        assert_eq!(read.sections.len(), 1);
+        assert_eq!(Container::from_bytes(&Container::new().to_bytes()).unwrap().preset, None);
+        container.algorithm = Algorithm::Lzmw;
+        let read = Container::from_bytes(&container.to_bytes()).unwrap();
+        assert_eq!(read.preset, Some(0xdeadbeef));
+        assert_eq!(read.algorithm, Algorithm::Lzmw);
+        assert_eq!(read.sections.len(), 1);
+        // Algorithm flag is unknown to version 3, fourth bit to every version
+        let mut bytes = container.to_bytes();
+        bytes[Container::MAGIC.len()] = 3;
+        assert!(Container::from_bytes(&bytes).is_err());
+        bytes[Container::MAGIC.len()] = Container::VERSION;
+        bytes[Container::MAGIC.len() + 1] |= 8;
+        assert!(Container::from_bytes(&bytes).is_err());
+    }
+}
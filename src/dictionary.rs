@@ -1,31 +1,55 @@
-use std::ops::{Index, IndexMut};
+use crate::bits::Bits;
+use crate::bit_io::BitsCursor;
+use crate::elias_delta::DeltaCode;
+use crate::universal_coding::{UniversalDecoder, UniversalEncoder};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
-#[derive(Debug)]
-pub struct Dictionary {
-    words: Vec<Vec<u8>>,
-    tree: WordsTree
+/**
+    Trie node, every word is a node and its bytes are read by walking up to the root,
+    so adding a word costs only the new nodes and no copy of its bytes
+*/
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    word: Option<usize>,
+    parent: usize,
+    byte: u8,
+    depth: usize,
 }
 
-impl Index<usize> for Dictionary {
-    type Output = Vec<u8>;
-
-    fn index(&self, index: usize) -> &Self::Output {
-        return &self.words[index];
-    }
+/**
+    Serialized form: magic bytes, version and Elias delta coded numbers: number of words,
+    then every word after the single bytes as index of its longest proper prefix
+    in dictionary, number of remaining bytes minus one and these bytes,
+    finally use count of every word.
+*/
+#[derive(Debug)]
+pub struct Dictionary {
+    nodes: Vec<Node>,
+    children: HashMap<(usize, u8), usize>,
+    // Node of every word
+    words: Vec<usize>,
+    uses: Vec<u64>,
 }
 
-impl IndexMut<usize> for Dictionary {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        return &mut self.words[index];
+impl Default for Dictionary {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Dictionary {
     const MAX_SIZE:usize = 4194304;
+    const ROOT: usize = 0;
+    pub const MAGIC: [u8; 4] = *b"LZWD";
+    pub const VERSION: u8 = 1;
+
     pub fn new() -> Self {
         let mut res = Self {
+            nodes: vec![Node { word: None, parent: Self::ROOT, byte: 0, depth: 0 }],
+            children: HashMap::new(),
             words: Vec::new(),
-            tree: WordsTree::new()
+            uses: Vec::new(),
         };
         for i in 0..=255 {
             res.add(vec![i]);
@@ -33,100 +57,254 @@ impl Dictionary {
         res
     }
 
-    pub fn vec_eq<A>(a: &Vec<A>, b: &Vec<A>) -> bool
-        where A: PartialEq
-    {
-        if a.len() != b.len() {
-            return false;
+    fn node(&self, seq: &[u8]) -> Option<usize> {
+        seq.iter().try_fold(Self::ROOT, |node, b| self.children.get(&(node, *b)).copied())
+    }
+
+    /**
+        Child of the node, created if missing
+    */
+    fn child(&mut self, node: usize, byte: u8) -> usize {
+        let next = self.nodes.len();
+        let res = *self.children.entry((node, byte)).or_insert(next);
+        if res == next {
+            let depth = self.nodes[node].depth + 1;
+            self.nodes.push(Node { word: None, parent: node, byte, depth });
         }
-        for i in 0..a.len() {
-            if a[i] != b[i] {
-                return false;
-            }
+        res
+    }
+
+    /**
+        Make word of the node, returns false when dictionary is full
+    */
+    fn mark_word(&mut self, node: usize) -> bool {
+        if self.nodes[node].word.is_some() {
+            return true;
         }
+        if self.words.len() >= Self::MAX_SIZE {
+            return false;
+        }
+        self.nodes[node].word = Some(self.words.len());
+        self.words.push(node);
+        self.uses.push(0);
         true
     }
 
-    pub fn word_position(&self, seq: &Vec<u8>) -> Option<usize> {
-        self.tree.get(seq.as_slice())
-        /*for i in 0..self.words.len() {
-            if Self::vec_eq(&seq, &self.words[i]) {
-                return Some(i);
+    pub fn word_position(&self, seq: &[u8]) -> Option<usize> {
+        self.nodes[self.node(seq)?].word
+    }
+
+    /**
+        Bytes of word with given index
+    */
+    pub fn word(&self, index: usize) -> Vec<u8> {
+        let mut res = vec![];
+        self.extend_with_word(index, &mut res);
+        res
+    }
+
+    pub fn word_len(&self, index: usize) -> usize {
+        self.nodes[self.words[index]].depth
+    }
+
+    /**
+        Append bytes of word with given index to `res`
+    */
+    pub fn extend_with_word(&self, index: usize, res: &mut Vec<u8>) {
+        let start = res.len();
+        let mut node = self.words[index];
+        while node != Self::ROOT {
+            res.push(self.nodes[node].byte);
+            node = self.nodes[node].parent;
+        }
+        res[start..].reverse();
+    }
+
+    /**
+        Index and length of the longest word which `data` starts with
+    */
+    pub fn longest_match(&self, data: &[u8]) -> Option<(usize, usize)> {
+        let mut node = Self::ROOT;
+        let mut res = None;
+        for b in data.iter() {
+            node = match self.children.get(&(node, *b)) {
+                Some(n) => *n,
+                None => break,
+            };
+            if let Some(index) = self.nodes[node].word {
+                res = Some((index, self.nodes[node].depth));
             }
         }
-        None*/
+        res
     }
 
     pub fn add(&mut self, seq: Vec<u8>) -> Option<usize> {
-        match self.word_position(&seq) {
-            None => {
-                return if self.words.len() < Self::MAX_SIZE {
-                    self.tree.add(seq.as_slice(), self.words.len());
-                    //eprintln!("{} {:?}", self.words.len(), seq);
-                    self.words.push(seq);
-                    Some(self.words.len())
-                } else {
-                    None
-                };
+        if let Some(v) = self.word_position(&seq) {
+            return Some(v);
+        }
+        if self.words.len() >= Self::MAX_SIZE {
+            return None;
+        }
+        let node = seq.iter().fold(Self::ROOT, |node, b| self.child(node, *b));
+        self.mark_word(node);
+        Some(self.words.len())
+    }
+
+    /**
+        Add `prefix` followed by every non empty prefix of `extension` in one walk of the trie,
+        returns number of new words
+    */
+    pub fn add_extensions(&mut self, prefix: &[u8], extension: &[u8]) -> usize {
+        let before = self.words.len();
+        let mut node = prefix.iter().fold(Self::ROOT, |node, b| self.child(node, *b));
+        for b in extension.iter() {
+            node = self.child(node, *b);
+            if !self.mark_word(node) {
+                break;
             }
-            Some(v) => Some(v)
         }
+        self.words.len() - before
     }
 
     pub fn len(&self) -> usize {
         self.words.len()
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum WordsTree{
-    Node(usize, Box<Vec<WordsTree>>),
-    Leaf
-}
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /**
+        Count word as emitted by coder
+    */
+    pub fn mark_used(&mut self, index: usize) {
+        self.uses[index] += 1;
+    }
 
-impl WordsTree{
-    fn new() -> Self{
-        WordsTree::Node(0, Box::new(vec![WordsTree::Leaf; 256]))
+    pub fn uses(&self, index: usize) -> u64 {
+        self.uses[index]
     }
 
-    fn add(&mut self, path: &[u8], index: usize){
-        match self {
-            WordsTree::Node(_, rest) => {
-                let x = path[0];
-                rest[x as usize].add(&path[1..], index);
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Bits::new();
+        // Writing to memory never fails
+        let mut put = |value: u64| DeltaCode.encode(value, &mut data).unwrap();
+        put(self.words.len() as u64);
+        for index in 256..self.words.len() {
+            // Prefix has to be read before the word, single bytes always are
+            let mut prefix = self.nodes[self.words[index]].parent;
+            while self.nodes[prefix].word.is_none_or(|w| w >= index) {
+                prefix = self.nodes[prefix].parent;
             }
-            WordsTree::Leaf => {
-                *self = WordsTree::Node(index, Box::new(vec![WordsTree::Leaf; 256]));
+            let word = self.word(index);
+            let len = self.nodes[prefix].depth;
+            put(self.nodes[prefix].word.unwrap() as u64);
+            put((word.len() - len - 1) as u64);
+            for b in word[len..].iter() {
+                put(*b as u64);
             }
         }
+        for u in self.uses.iter() {
+            put(*u);
+        }
+        let mut res = Self::MAGIC.to_vec();
+        res.push(Self::VERSION);
+        res.extend_from_slice(data.bytes());
+        res
     }
 
-    fn get(&self, path: &[u8]) -> Option<usize>{
-        return match self {
-            WordsTree::Node(index, rest) => {
-                if path.is_empty() {
-                    return Some(*index);
-                }
-                let x = path[0];
-                return rest[x as usize].get(&path[1..])
+    pub fn is_dictionary(bytes: &[u8]) -> bool {
+        bytes.len() > Self::MAGIC.len() && bytes[..Self::MAGIC.len()] == Self::MAGIC
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !Self::is_dictionary(bytes) {
+            return Err("Missing dictionary header".to_string());
+        }
+        if bytes[Self::MAGIC.len()] != Self::VERSION {
+            return Err(format!("Unsupported dictionary version {}", bytes[Self::MAGIC.len()]));
+        }
+        let data = Bits::from_bytes(bytes[Self::MAGIC.len() + 1..].to_vec(), (bytes.len() - Self::MAGIC.len() - 1) * 8);
+        let mut cursor = BitsCursor::new(&data, 0);
+        let damaged = || "Damaged dictionary".to_string();
+        let mut get = || -> Result<u64, String> { DeltaCode.decode(&mut cursor).ok_or_else(damaged) };
+        let mut res = Self::new();
+        let len = get()?;
+        if len < 256 || len > Self::MAX_SIZE as u64 {
+            return Err(damaged());
+        }
+        for i in 256..len as usize {
+            let prefix = get()?;
+            let rest = get()?;
+            if prefix >= i as u64 || rest >= data.len() as u64 {
+                return Err(damaged());
+            }
+            let mut word = res.word(prefix as usize);
+            for _ in 0..=rest {
+                word.push(u8::try_from(get()?).map_err(|_| damaged())?);
             }
-            WordsTree::Leaf => {
-                None
+            if res.word_position(&word).is_some() {
+                return Err(damaged());
             }
+            res.add(word);
         }
+        for u in res.uses.iter_mut() {
+            *u = get()?;
+        }
+        Ok(res)
     }
 }
 
 #[cfg(test)]
 mod dict_test {
-    use crate::dictionary::WordsTree;
+    use crate::algorithm::Algorithm;
+    use crate::dictionary::Dictionary;
 
     #[test]
     fn tree_test() {
-        let mut tree = WordsTree::new();
-        tree.add(&[0], 0);
-        tree.add(&[1], 1);
-        tree.add(&[0, 1], 2);
-        assert_eq!(tree.get(&[0,1]), Some(2))
+        let mut dictionary = Dictionary::new();
+        assert_eq!(dictionary.len(), 256);
+        dictionary.add(vec![0, 1]);
+        assert_eq!(dictionary.word_position(&[0, 1]), Some(256));
+        dictionary.add(vec![1, 2, 3]);
+        assert_eq!(dictionary.word_position(&[1, 2]), None);
+        assert_eq!(dictionary.word(257), [1, 2, 3]);
+        assert_eq!(dictionary.word_len(257), 3);
+        assert_eq!(dictionary.longest_match(&[1, 2, 3, 4]), Some((257, 3)));
+        assert_eq!(dictionary.longest_match(&[1, 2, 4]), Some((1, 1)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn extensions_test() {
+        let mut dictionary = Dictionary::new();
+        assert_eq!(dictionary.add_extensions(b"ab", b"cd"), 2);
+        assert_eq!(dictionary.word_position(b"abc"), Some(256));
+        assert_eq!(dictionary.word_position(b"abcd"), Some(257));
+        assert_eq!(dictionary.word_position(b"ab"), None);
+        assert_eq!(dictionary.add_extensions(b"a", b"bcde"), 2);
+        assert_eq!(dictionary.word(258), b"ab");
+        assert_eq!(dictionary.word(259), b"abcde");
+    }
+
+    #[test]
+    fn serialization_test() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT\n\x00\xffabababababab".repeat(20);
+        for algorithm in Algorithm::ALL.iter() {
+            let mut dictionary = Dictionary::new();
+            algorithm.encode(&data, &mut dictionary, |_| {});
+            dictionary.add_extensions(b"xyz", b"uvw");
+            assert!(dictionary.uses(b'T' as usize) > 0);
+            let bytes = dictionary.to_bytes();
+            if *algorithm == Algorithm::Lzw {
+                assert!(bytes.len() < data.len());
+            }
+            let read = Dictionary::from_bytes(&bytes).unwrap();
+            assert_eq!(read.len(), dictionary.len());
+            for i in 0..read.len() {
+                assert_eq!(read.word(i), dictionary.word(i));
+                assert_eq!(read.uses(i), dictionary.uses(i));
+            }
+            assert!(Dictionary::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+        }
+    }
+}
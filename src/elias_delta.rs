@@ -1,7 +1,35 @@
 use crate::bits::Bits;
-use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable};
+use crate::elias_gamma::GammaCode;
+use crate::bit_io::{BitRead, BitWrite, BitsCursor};
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect, decode_bits_into, CodewordLength, UniversalEncoder, UniversalDecoder};
+use crate::unsigned::{Unsigned, read_wide, write_wide};
+use std::io;
 use std::path::Path;
 
+/**
+    Elias delta code: bit length in Elias gamma code followed by the number without its leading one
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeltaCode;
+
+impl<T: Unsigned> UniversalEncoder<T> for DeltaCode {
+    fn encode<W: BitWrite + ?Sized>(&self, n: T, w: &mut W) -> io::Result<()> {
+        let size = n.successor_size();
+        GammaCode.encode(size - 1, w)?;
+        write_wide(w, n.to_u128().wrapping_add(1), size - 1)
+    }
+}
+
+impl<T: Unsigned> UniversalDecoder<T> for DeltaCode {
+    fn decode<R: BitRead + ?Sized>(&self, r: &mut R) -> Option<T> {
+        let size: u32 = GammaCode.decode(r)?;
+        if size > T::BITS {
+            return None;
+        }
+        T::from_successor(size, read_wide(r, size)?)
+    }
+}
+
 #[derive(Debug)]
 pub struct EliasDelta {
     data: Bits,
@@ -9,24 +37,8 @@ pub struct EliasDelta {
 }
 
 impl EliasDelta {
-    const LAST_U64: u64 = 0x8000000000000000;
-
-    fn number_size(a: u64) -> u32 {
-        let mut t = Self::LAST_U64;
-        for i in (1_u32..=64).rev() {
-            if a & t != 0 {
-                return i;
-            }
-            t >>= 1;
-        }
-        0
-    }
-
     pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
-        Ok(Self {
-            data: Bits::read_from_file(path)?,
-            index: 0,
-        })
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
     }
 }
 
@@ -37,74 +49,47 @@ impl Creatable for EliasDelta {
             index: 0,
         }
     }
+
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
+        }
+    }
+}
+
+impl CodewordLength for EliasDelta {
+    fn encoded_len(n: u64) -> u32 {
+        let n = n.successor_size();
+        2 * (32 - n.leading_zeros()) + n - 2
+    }
 }
 
 impl UniversalCode for EliasDelta {
     fn get(&mut self) -> Option<u64> {
-        let mut t = 1_u64;
-        let mut n = 0;
-        let mut res = 0;
-        loop {
-            match self.data.get(self.index) {
-                Some(true) => break,
-                Some(false) => {
-                    self.index += 1;
-                    t <<= 1;
-                }
-                None => return None,
-            }
-        }
-        t <<= 1;
-        while t > 1 {
-            t >>= 1;
-            if self.data.get(self.index).unwrap() {
-                n += t;
-            }
-            self.index += 1;
-        }
-        t = 1_u64 << n - 1;
-        res += t;
-        while t > 1 {
-            t >>= 1;
-            if self.data.get(self.index).unwrap() {
-                res += t;
-            }
-            self.index += 1;
-        }
-        Some(res - 1)
+        let mut cursor = BitsCursor::new(&self.data, self.index);
+        let res = DeltaCode.decode(&mut cursor)?;
+        self.index = cursor.index;
+        Some(res)
     }
 
-    fn add(&mut self, mut code: u64) {
-        code += 1;
-        let n = Self::number_size(code) as u64;
-        let m = Self::number_size(n);
-        for _ in 1..m {
-            self.data.push(false);
-        }
-        let mut t = 1_u64 << m as u64 - 1;
-        while t > 0 {
-            if n & t == 0 {
-                self.data.push(false);
-            } else {
-                self.data.push(true);
-            }
-            t >>= 1;
-        }
-        t = 1_u64 << n as u64 >> 2;
-        while t > 0 {
-            if code & t == 0 {
-                self.data.push(false);
-            } else {
-                self.data.push(true);
-            }
-            t >>= 1;
-        }
+    fn decode_into(&mut self, res: &mut Vec<u64>, count: usize) -> usize {
+        decode_bits_into(&DeltaCode, &self.data, &mut self.index, res, count)
+    }
+
+    fn add(&mut self, code: u64) {
+        // Writing to memory never fails
+        DeltaCode.encode(code, &mut self.data).unwrap();
     }
 
     fn save_to_file(&self, path: String) -> Result<(), String>{
         self.data.save_to_file(path)
     }
 
+    fn to_bits(&self) -> Bits {
+        self.data.clone()
+    }
+
     fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
         Box::new(UniversalCodeIter {
             c: self
@@ -124,9 +109,11 @@ impl UniversalCode for EliasDelta {
     }
 }
 
+impl_collect!(EliasDelta);
+
 #[cfg(test)]
 mod delta_test {
-    use crate::universal_coding::{UniversalCode, Creatable};
+    use crate::universal_coding::{UniversalCode, Creatable, CodewordLength, boundary_values};
 
     #[test]
     fn delta_test() {
@@ -143,4 +130,43 @@ mod delta_test {
         assert_eq!(c.get(), Some(1323123213123));
         assert_eq!(c.get(), Some(3312312345324423));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn delta_len_test() {
+        let mut c = super::EliasDelta::new();
+        let values: Vec<u64> = (0..1000).chain(boundary_values()).collect();
+        for n in values.iter().copied() {
+            let before = c.len();
+            c.add(n);
+            assert_eq!((c.len() - before) as u32, super::EliasDelta::encoded_len(n), "length of {}", n);
+        }
+        for n in values {
+            assert_eq!(c.get(), Some(n));
+        }
+    }
+
+    #[test]
+    fn delta_wide_test() {
+        use crate::bit_io::BitsCursor;
+        use crate::bits::Bits;
+        use crate::unsigned::wide_boundary_values;
+        use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+        use super::DeltaCode;
+        let mut data = Bits::new();
+        for n in 0..=u8::MAX {
+            DeltaCode.encode(n, &mut data).unwrap();
+        }
+        for n in wide_boundary_values() {
+            DeltaCode.encode(n, &mut data).unwrap();
+        }
+        DeltaCode.encode(256_u16, &mut data).unwrap();
+        let mut cursor = BitsCursor::new(&data, 0);
+        for n in 0..=u8::MAX {
+            assert_eq!(DeltaCode.decode(&mut cursor), Some(n));
+        }
+        for n in wide_boundary_values() {
+            assert_eq!(DeltaCode.decode(&mut cursor), Some(n));
+        }
+        assert_eq!(DeltaCode.decode(&mut cursor), None::<u8>);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::bits::Bits;
+
+/**
+    Elias-Fano representation of non decreasing numbers.
+    Every number is split into `low_bits` lowest bits stored directly and the high part
+    stored in unary as a bit vector, where number i sets bit `(value >> low_bits) + i`.
+    Positions of every `SAMPLE`-th one and zero of the high part are kept,
+    so `get` and `next_geq` only scan a few words.
+*/
+#[derive(Debug, Clone)]
+pub struct EliasFano {
+    low: Bits,
+    high: Bits,
+    low_bits: u32,
+    len: usize,
+    ones: Vec<usize>,
+    zeros: Vec<usize>,
+}
+
+impl EliasFano {
+    const SAMPLE: usize = 64;
+
+    pub fn from_sorted(values: &[u64]) -> Result<Self, String> {
+        if let Some(w) = values.windows(2).find(|w| w[1] < w[0]) {
+            return Err(format!("Value {} is smaller than previous {}", w[1], w[0]));
+        }
+        let len = values.len();
+        let max = values.last().copied().unwrap_or(0);
+        let ratio = (max as u128 + 1) / len.max(1) as u128;
+        let low_bits = if ratio > 1 { (127 - ratio.leading_zeros()).min(63) } else { 0 };
+        let mut res = Self {
+            low: Bits::new(),
+            high: Bits::new(),
+            low_bits,
+            len,
+            ones: Vec::new(),
+            zeros: Vec::new(),
+        };
+        let mut bucket = 0;
+        let mut zeros = 0;
+        for (i, v) in values.iter().enumerate() {
+            while bucket < v >> low_bits {
+                res.push_high(false, zeros);
+                zeros += 1;
+                bucket += 1;
+            }
+            res.push_high(true, i);
+            res.low.push_bits(*v, low_bits);
+        }
+        // Closing zeros, so every bucket up to the largest one ends with zero
+        while bucket <= max >> low_bits {
+            res.push_high(false, zeros);
+            zeros += 1;
+            bucket += 1;
+        }
+        Ok(res)
+    }
+
+    fn push_high(&mut self, bit: bool, count: usize) {
+        if count.is_multiple_of(Self::SAMPLE) {
+            if bit {
+                self.ones.push(self.high.len());
+            } else {
+                self.zeros.push(self.high.len());
+            }
+        }
+        self.high.push(bit);
+    }
+
+    /**
+        Position of the bit equal to `bit` with given rank in the high part
+    */
+    fn select(&self, bit: bool, rank: usize) -> Option<usize> {
+        let samples = if bit { &self.ones } else { &self.zeros };
+        let mut position = *samples.get(rank / Self::SAMPLE)?;
+        let mut left = rank % Self::SAMPLE;
+        while position < self.high.len() {
+            let n = (self.high.len() - position).min(64) as u32;
+            // Bits of the word aligned to the top
+            let mut word = self.high.get_bits(position, n)? << (64 - n);
+            if !bit {
+                word = !word & (u64::MAX << (64 - n));
+            }
+            let count = word.count_ones() as usize;
+            if left < count {
+                for _ in 0..left {
+                    word &= !((1 << 63) >> word.leading_zeros());
+                }
+                return Some(position + word.leading_zeros() as usize);
+            }
+            left -= count;
+            position += n as usize;
+        }
+        None
+    }
+
+    fn value(&self, index: usize, position: usize) -> u64 {
+        let high = (position - index) as u64;
+        let low = self.low.get_bits(index * self.low_bits as usize, self.low_bits).unwrap_or(0);
+        (high << self.low_bits) | low
+    }
+
+    pub fn get(&self, index: usize) -> Option<u64> {
+        if index >= self.len {
+            return None;
+        }
+        Some(self.value(index, self.select(true, index)?))
+    }
+
+    /**
+        Index and value of the first number greater or equal to `x`
+    */
+    pub fn next_geq(&self, x: u64) -> Option<(usize, u64)> {
+        let bucket = (x >> self.low_bits) as usize;
+        // Numbers of the bucket start after its preceding zero
+        let mut position = match bucket {
+            0 => 0,
+            b => self.select(false, b - 1)? + 1,
+        };
+        let mut index = position - bucket;
+        while index < self.len {
+            if self.high.get(position)? {
+                let value = self.value(index, position);
+                if value >= x {
+                    return Some((index, value));
+                }
+                index += 1;
+            }
+            position += 1;
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /**
+        Size of both parts in bits, samples are not counted
+    */
+    pub fn bits_len(&self) -> usize {
+        self.low.len() + self.high.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=u64> + '_ {
+        (0..self.len).map(move |i| self.get(i).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod elias_fano_test {
+    use super::EliasFano;
+
+    #[test]
+    fn elias_fano_test() {
+        let values: Vec<u64> = (0..3000).map(|i| i * i + i % 5).collect();
+        let ef = EliasFano::from_sorted(&values).unwrap();
+        assert_eq!(ef.len(), values.len());
+        for (i, v) in values.iter().enumerate() {
+            assert_eq!(ef.get(i), Some(*v));
+        }
+        assert_eq!(ef.get(values.len()), None);
+        assert_eq!(ef.iter().collect::<Vec<u64>>(), values);
+        assert!(ef.bits_len() < values.len() * 16);
+        assert!(EliasFano::from_sorted(&[3, 2]).is_err());
+        let edges = EliasFano::from_sorted(&[0, 0, 1, u64::MAX]).unwrap();
+        assert_eq!(edges.iter().collect::<Vec<u64>>(), [0, 0, 1, u64::MAX]);
+        assert!(EliasFano::from_sorted(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn next_geq_test() {
+        let values: Vec<u64> = (0..2000).map(|i| i * 37 + (i * i) % 11).collect();
+        let ef = EliasFano::from_sorted(&values).unwrap();
+        for x in (0..75000).step_by(7) {
+            let expected = values.iter().position(|v| *v >= x).map(|i| (i, values[i]));
+            assert_eq!(ef.next_geq(x), expected, "next_geq({})", x);
+        }
+        let edges = EliasFano::from_sorted(&[5, 5, u64::MAX]).unwrap();
+        assert_eq!(edges.next_geq(0), Some((0, 5)));
+        assert_eq!(edges.next_geq(6), Some((2, u64::MAX)));
+        assert_eq!(edges.next_geq(u64::MAX), Some((2, u64::MAX)));
+    }
+}
@@ -1,7 +1,37 @@
 use crate::bits::Bits;
-use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable};
+use crate::bit_io::{BitRead, BitWrite, BitsCursor};
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect, decode_bits_into, CodewordLength, UniversalEncoder, UniversalDecoder};
+use crate::unsigned::{Unsigned, read_wide, write_wide};
+use std::io;
 use std::path::Path;
 
+/**
+    Elias gamma code: bit length in unary followed by the number
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GammaCode;
+
+impl<T: Unsigned> UniversalEncoder<T> for GammaCode {
+    fn encode<W: BitWrite + ?Sized>(&self, n: T, w: &mut W) -> io::Result<()> {
+        let size = n.successor_size();
+        for _ in 1..size {
+            w.write_bit(false)?;
+        }
+        w.write_bit(true)?;
+        write_wide(w, n.to_u128().wrapping_add(1), size - 1)
+    }
+}
+
+impl<T: Unsigned> UniversalDecoder<T> for GammaCode {
+    fn decode<R: BitRead + ?Sized>(&self, r: &mut R) -> Option<T> {
+        let zeros = r.read_unary()?;
+        if zeros > T::BITS {
+            return None;
+        }
+        T::from_successor(zeros, read_wide(r, zeros)?)
+    }
+}
+
 #[derive(Debug)]
 pub struct EliasGamma {
     data: Bits,
@@ -9,24 +39,8 @@ pub struct EliasGamma {
 }
 
 impl EliasGamma {
-    const LAST_U64: u64 = 0x8000000000000000;
-
-    fn number_size(a: u64) -> u32 {
-        let mut t = Self::LAST_U64;
-        for i in (1_u32..=64).rev() {
-            if a & t != 0 {
-                return i;
-            }
-            t >>= 1;
-        }
-        0
-    }
-
     pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
-        Ok(Self {
-            data: Bits::read_from_file(path)?,
-            index: 0,
-        })
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
     }
 }
 
@@ -37,54 +51,46 @@ impl Creatable for EliasGamma {
             index: 0,
         }
     }
+
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
+        }
+    }
+}
+
+impl CodewordLength for EliasGamma {
+    fn encoded_len(n: u64) -> u32 {
+        2 * n.successor_size() - 1
+    }
 }
 
 impl UniversalCode for EliasGamma {
     fn get(&mut self) -> Option<u64> {
-        let mut t = 1_u64;
-        let mut res = 0;
-        loop {
-            match self.data.get(self.index) {
-                Some(true) => break,
-                Some(false) => {
-                    self.index += 1;
-                    t <<= 1;
-                }
-                None => return None,
-            }
-        }
-        t <<= 1;
-        while t > 1 {
-            t >>= 1;
-            if self.data.get(self.index).unwrap() {
-                res += t;
-            }
-            self.index += 1;
-        }
-        Some(res - 1)
+        let mut cursor = BitsCursor::new(&self.data, self.index);
+        let res = GammaCode.decode(&mut cursor)?;
+        self.index = cursor.index;
+        Some(res)
     }
 
-    fn add(&mut self, mut code: u64) {
-        code += 1;
-        let n = Self::number_size(code);
-        for _ in 1..n {
-            self.data.push(false);
-        }
-        let mut t = 1_u64 << n as u64 - 1;
-        while t > 0 {
-            if code & t == 0 {
-                self.data.push(false);
-            } else {
-                self.data.push(true);
-            }
-            t >>= 1;
-        }
+    fn decode_into(&mut self, res: &mut Vec<u64>, count: usize) -> usize {
+        decode_bits_into(&GammaCode, &self.data, &mut self.index, res, count)
+    }
+
+    fn add(&mut self, code: u64) {
+        // Writing to memory never fails
+        GammaCode.encode(code, &mut self.data).unwrap();
     }
 
     fn save_to_file(&self, path: String) -> Result<(), String>{
         self.data.save_to_file(path)
     }
 
+    fn to_bits(&self) -> Bits {
+        self.data.clone()
+    }
+
     fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
         Box::new(UniversalCodeIter {
             c: self
@@ -104,9 +110,11 @@ impl UniversalCode for EliasGamma {
     }
 }
 
+impl_collect!(EliasGamma);
+
 #[cfg(test)]
 mod gamma_test {
-    use crate::universal_coding::{UniversalCode, Creatable};
+    use crate::universal_coding::{UniversalCode, Creatable, CodewordLength, boundary_values};
 
     #[test]
     fn gamma_test() {
@@ -123,4 +131,51 @@ mod gamma_test {
         assert_eq!(c.get(), Some(1323123213123));
         assert_eq!(c.get(), Some(3312312345324423));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn gamma_len_test() {
+        let mut c = super::EliasGamma::new();
+        let values: Vec<u64> = (0..1000).chain(boundary_values()).collect();
+        for n in values.iter().copied() {
+            let before = c.len();
+            c.add(n);
+            assert_eq!((c.len() - before) as u32, super::EliasGamma::encoded_len(n), "length of {}", n);
+        }
+        for n in values {
+            assert_eq!(c.get(), Some(n));
+        }
+    }
+
+    #[test]
+    fn gamma_wide_test() {
+        use crate::bit_io::BitsCursor;
+        use crate::bits::Bits;
+        use crate::unsigned::wide_boundary_values;
+        use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+        use super::GammaCode;
+        let mut data = Bits::new();
+        for n in 0..=u8::MAX {
+            GammaCode.encode(n, &mut data).unwrap();
+        }
+        for n in wide_boundary_values() {
+            GammaCode.encode(n, &mut data).unwrap();
+        }
+        GammaCode.encode(256_u16, &mut data).unwrap();
+        let mut cursor = BitsCursor::new(&data, 0);
+        for n in 0..=u8::MAX {
+            assert_eq!(GammaCode.decode(&mut cursor), Some(n));
+        }
+        for n in wide_boundary_values() {
+            assert_eq!(GammaCode.decode(&mut cursor), Some(n));
+        }
+        assert_eq!(GammaCode.decode(&mut cursor), None::<u8>);
+    }
+
+    #[test]
+    fn collect_test() {
+        let mut c: super::EliasGamma = (0..100).collect();
+        c.extend([u64::MAX, 7]);
+        let decoded: Vec<u64> = c.into_iter().collect();
+        assert_eq!(decoded, (0..100).chain([u64::MAX, 7]).collect::<Vec<u64>>());
+    }
+}
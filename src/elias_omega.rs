@@ -1,32 +1,107 @@
 use crate::bits::Bits;
-use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable};
+use crate::bit_io::{BitRead, BitWrite, BitsCursor};
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect, decode_bits_into, CodewordLength, UniversalEncoder, UniversalDecoder};
+use crate::unsigned::{Unsigned, read_wide, write_wide};
+use std::io;
 use std::path::Path;
 
-#[derive(Debug)]
-pub struct EliasOmega {
-    data: Bits,
-    index: usize,
+/**
+    Elias omega code: recursive bit lengths followed by the number and closing zero
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OmegaCode;
+
+impl OmegaCode {
+    const PEEK: u32 = 56;
+
+    fn group_size(k: u128) -> u32 {
+        128 - k.leading_zeros()
+    }
+
+    /**
+        Size and low bits of the last group and length of codeword in top of `PEEK` peeked bits,
+        `None` when the codeword does not end inside them or does not fit in `bits`
+    */
+    fn decode_peeked(word: u64, bits: u32) -> Option<(u32, u64, u32)> {
+        let mut size = 0;
+        let mut low = 0_u64;
+        let mut used = 0;
+        while used < Self::PEEK {
+            used += 1;
+            if word >> (Self::PEEK - used) & 1 == 0 {
+                return Some((size, low, used));
+            }
+            if size >= bits.min(63) {
+                return None;
+            }
+            let n = (1 << size) | low;
+            if n > bits as u64 || n > (Self::PEEK - used) as u64 {
+                return None;
+            }
+            size = n as u32;
+            used += size;
+            low = word >> (Self::PEEK - used) & ((1 << size) - 1);
+        }
+        None
+    }
 }
 
-impl EliasOmega {
-    const LAST_U64: u64 = 0x8000000000000000;
+impl<T: Unsigned> UniversalEncoder<T> for OmegaCode {
+    fn encode<W: BitWrite + ?Sized>(&self, n: T, w: &mut W) -> io::Result<()> {
+        // Lengths are small, only the last group n + 1 may not fit in u128
+        let size = n.successor_size();
+        let mut groups = vec![];
+        let mut k = size as u128 - 1;
+        while k > 1 {
+            groups.push(k);
+            k = Self::group_size(k) as u128 - 1;
+        }
+        for group in groups.into_iter().rev() {
+            w.write_bit(true)?;
+            w.write_bits(group as u64, Self::group_size(group) - 1)?;
+        }
+        if size > 1 {
+            w.write_bit(true)?;
+            write_wide(w, n.to_u128().wrapping_add(1), size - 1)?;
+        }
+        w.write_bit(false)
+    }
+}
 
-    fn number_size(a: u64) -> u32 {
-        let mut t = Self::LAST_U64;
-        for i in (1_u32..=64).rev() {
-            if a & t != 0 {
-                return i;
+impl<T: Unsigned> UniversalDecoder<T> for OmegaCode {
+    fn decode<R: BitRead + ?Sized>(&self, r: &mut R) -> Option<T> {
+        // Whole codeword from one peek, long or invalid ones are read group by group
+        if let Some((size, low, used)) = r.peek_bits(Self::PEEK).and_then(|w| Self::decode_peeked(w, T::BITS)) {
+            r.read_bits(used)?;
+            return T::from_successor(size, low as u128);
+        }
+        // Last group is 2^size + low
+        let mut size = 0;
+        let mut low = 0_u128;
+        while r.read_bit()? {
+            if size >= T::BITS {
+                return None;
+            }
+            let n = (1 << size) | low;
+            if n > T::BITS as u128 {
+                return None;
             }
-            t >>= 1;
+            size = n as u32;
+            low = read_wide(r, size)?;
         }
-        0
+        T::from_successor(size, low)
     }
+}
 
+#[derive(Debug)]
+pub struct EliasOmega {
+    data: Bits,
+    index: usize,
+}
+
+impl EliasOmega {
     pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
-        Ok(Self {
-            data: Bits::read_from_file(path)?,
-            index: 0,
-        })
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
     }
 }
 
@@ -37,62 +112,53 @@ impl Creatable for EliasOmega {
             index: 0,
         }
     }
-}
 
-impl UniversalCode for EliasOmega {
-    fn get(&mut self) -> Option<u64> {
-        let mut n = 1;
-        let mut t;
-        loop {
-            match self.data.get(self.index) {
-                None => return None,
-                Some(false) => {
-                    self.index += 1;
-                    break;
-                }
-                Some(true) => {
-                    t = 1 << n;
-                    self.index += 1;
-                    n = t;
-                    t >>= 1;
-                }
-            }
-            while t > 0 {
-                if self.data.get(self.index).unwrap() {
-                    n += t;
-                }
-                self.index += 1;
-                t >>= 1;
-            }
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
         }
-        Some(n - 1)
     }
+}
 
-    fn add(&mut self, mut code: u64) {
-        code += 1;
-        let mut buffer = vec![false];
-        let mut k = code;
+impl CodewordLength for EliasOmega {
+    fn encoded_len(n: u64) -> u32 {
+        let mut res = 1;
+        let mut k = n as u128 + 1;
         while k > 1 {
-            let size = Self::number_size(k) as u64;
-            while k > 0 {
-                if k % 2 == 1 {
-                    buffer.push(true);
-                } else {
-                    buffer.push(false);
-                }
-                k >>= 1;
-            }
-            k = size - 1;
-        }
-        for bit in buffer.into_iter().rev() {
-            self.data.push(bit);
+            let size = OmegaCode::group_size(k);
+            res += size;
+            k = size as u128 - 1;
         }
+        res
+    }
+}
+
+impl UniversalCode for EliasOmega {
+    fn get(&mut self) -> Option<u64> {
+        let mut cursor = BitsCursor::new(&self.data, self.index);
+        let res = OmegaCode.decode(&mut cursor)?;
+        self.index = cursor.index;
+        Some(res)
+    }
+
+    fn decode_into(&mut self, res: &mut Vec<u64>, count: usize) -> usize {
+        decode_bits_into(&OmegaCode, &self.data, &mut self.index, res, count)
+    }
+
+    fn add(&mut self, code: u64) {
+        // Writing to memory never fails
+        OmegaCode.encode(code, &mut self.data).unwrap();
     }
 
     fn save_to_file(&self, path: String) -> Result<(), String>{
         self.data.save_to_file(path)
     }
 
+    fn to_bits(&self) -> Bits {
+        self.data.clone()
+    }
+
     fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
         Box::new(UniversalCodeIter {
             c: self
@@ -112,9 +178,11 @@ impl UniversalCode for EliasOmega {
     }
 }
 
+impl_collect!(EliasOmega);
+
 #[cfg(test)]
 mod omega_test {
-    use crate::universal_coding::{UniversalCode, Creatable};
+    use crate::universal_coding::{UniversalCode, Creatable, CodewordLength, boundary_values};
 
     #[test]
     fn omega_test() {
@@ -131,4 +199,43 @@ mod omega_test {
         c.add(0);
         assert_eq!(c.get(), Some(0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn omega_len_test() {
+        let mut c = super::EliasOmega::new();
+        let values: Vec<u64> = (0..1000).chain(boundary_values()).collect();
+        for n in values.iter().copied() {
+            let before = c.len();
+            c.add(n);
+            assert_eq!((c.len() - before) as u32, super::EliasOmega::encoded_len(n), "length of {}", n);
+        }
+        for n in values {
+            assert_eq!(c.get(), Some(n));
+        }
+    }
+
+    #[test]
+    fn omega_wide_test() {
+        use crate::bit_io::BitsCursor;
+        use crate::bits::Bits;
+        use crate::unsigned::wide_boundary_values;
+        use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+        use super::OmegaCode;
+        let mut data = Bits::new();
+        for n in 0..=u8::MAX {
+            OmegaCode.encode(n, &mut data).unwrap();
+        }
+        for n in wide_boundary_values() {
+            OmegaCode.encode(n, &mut data).unwrap();
+        }
+        OmegaCode.encode(256_u16, &mut data).unwrap();
+        let mut cursor = BitsCursor::new(&data, 0);
+        for n in 0..=u8::MAX {
+            assert_eq!(OmegaCode.decode(&mut cursor), Some(n));
+        }
+        for n in wide_boundary_values() {
+            assert_eq!(OmegaCode.decode(&mut cursor), Some(n));
+        }
+        assert_eq!(OmegaCode.decode(&mut cursor), None::<u8>);
+    }
+}
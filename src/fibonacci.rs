@@ -1,108 +1,208 @@
 use crate::bits::Bits;
-use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable};
+use crate::bit_io::{BitRead, BitWrite, BitsCursor};
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect, decode_bits_into, CodewordLength, UniversalEncoder, UniversalDecoder};
+use crate::unsigned::Unsigned;
+use std::io;
 use std::path::Path;
 
-#[derive(Debug)]
-pub struct Fibonacci {
-    data: Bits,
-    index: usize,
-    fib: Vec<u64>,
-}
+/**
+    Fibonacci numbers 1, 2, 3, 5, ... up to the largest one not greater than 2^128
+*/
+const FIB: [u128; 185] = fib_table();
 
-impl Fibonacci {
-    pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
-        Ok(Self {
-            data: Bits::read_from_file(path)?,
-            index: 0,
-            fib: vec![1, 1],
-        })
+const fn fib_table() -> [u128; 185] {
+    let mut res = [1_u128; 185];
+    res[1] = 2;
+    let mut i = 2;
+    while i < res.len() {
+        res[i] = res[i - 1] + res[i - 2];
+        i += 1;
     }
+    res
+}
 
-    fn generate_fib_till(&mut self, code: u64) {
-        while self.fib[self.fib.len() - 1] < code {
-            self.fib.push(self.fib[self.fib.len() - 1] + self.fib[self.fib.len() - 2]);
+/**
+    Decoding of one byte of Fibonacci code, depending on the last bit of previous byte.
+    Value of bits at offsets k from Fibonacci index i is `a * FIB[i + 1] + b * FIB[i]`,
+    because F(i + k) = F(k) * F(i + 1) + F(k - 1) * F(i) (Klein's method).
+*/
+#[derive(Debug, Clone, Copy)]
+struct ByteStep {
+    a: u64,
+    b: u64,
+    // Bits consumed up to closing one, 0 when codeword continues
+    end: u32,
+    last: bool,
+}
+
+const BYTE_STEPS: [[ByteStep; 256]; 2] = byte_steps();
+
+const fn byte_steps() -> [[ByteStep; 256]; 2] {
+    // F(k) and F(k - 1) for k = 0..8, with F(-1) = 1
+    const F: [u64; 9] = [1, 0, 1, 1, 2, 3, 5, 8, 13];
+    let empty = ByteStep { a: 0, b: 0, end: 0, last: false };
+    let mut res = [[empty; 256]; 2];
+    let mut prev_bit = 0;
+    while prev_bit < 2 {
+        let mut byte = 0;
+        while byte < 256 {
+            let mut step = empty;
+            let mut prev = prev_bit == 1;
+            let mut k = 0;
+            while k < 8 {
+                let bit = (byte >> (7 - k)) & 1 == 1;
+                if bit && prev {
+                    step.end = k as u32 + 1;
+                    break;
+                }
+                if bit {
+                    step.a += F[k + 1];
+                    step.b += F[k];
+                }
+                prev = bit;
+                k += 1;
+            }
+            step.last = prev;
+            res[prev_bit][byte] = step;
+            byte += 1;
         }
+        prev_bit += 1;
     }
+    res
+}
+
+/**
+    Fibonacci code: Zeckendorf representation from the smallest term, closed with additional one
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FibonacciCode;
 
-    fn generate_fib(&mut self, n: usize) {
-        while self.fib.len() <= n {
-            self.fib.push(self.fib[self.fib.len() - 1] + self.fib[self.fib.len() - 2]);
+impl<T: Unsigned> UniversalEncoder<T> for FibonacciCode {
+    fn encode<W: BitWrite + ?Sized>(&self, n: T, w: &mut W) -> io::Result<()> {
+        // Largest term is found for n + 1, which may not fit in u128
+        let n = n.to_u128();
+        let top = FIB.partition_point(|f| f - 1 <= n) - 1;
+        let mut code = n - (FIB[top] - 1);
+        let mut used = [false; FIB.len()];
+        used[top] = true;
+        for i in (0..top).rev() {
+            if FIB[i] <= code {
+                code -= FIB[i];
+                used[i] = true;
+            }
         }
+        for bit in used[..=top].iter() {
+            w.write_bit(*bit)?;
+        }
+        w.write_bit(true)
     }
+}
 
-    fn get_largest_smaller_fib(&mut self, code: u64) -> usize {
-        self.generate_fib_till(code + 1);
-        let mut i = 1;
+impl<T: Unsigned> UniversalDecoder<T> for FibonacciCode {
+    fn decode<R: BitRead + ?Sized>(&self, r: &mut R) -> Option<T> {
+        // Sum of terms minus one, so 2^128 for u128::MAX never has to be stored
+        let mut res: Option<u128> = None;
+        let mut add = |term: u128| -> Option<()> {
+            res = Some(match res {
+                Some(r) => r.checked_add(term)?,
+                None => term - 1,
+            });
+            Some(())
+        };
+        let mut prev = false;
+        let mut i = 0;
         loop {
-            if code < self.fib[i] {
-                return i - 1;
+            // Whole bytes through table while Fibonacci numbers of the byte are known
+            if i + 8 < FIB.len() {
+                if let Some(byte) = r.peek_bits(8) {
+                    let step = BYTE_STEPS[prev as usize][byte as usize];
+                    let term = step.a as u128 * FIB[i + 1] + step.b as u128 * FIB[i];
+                    if term > 0 {
+                        add(term)?;
+                    }
+                    if step.end > 0 {
+                        r.read_bits(step.end)?;
+                        return T::from_u128(res?);
+                    }
+                    r.read_bits(8)?;
+                    prev = step.last;
+                    i += 8;
+                    continue;
+                }
+            }
+            let bit = r.read_bit()?;
+            if bit {
+                if prev {
+                    return T::from_u128(res?);
+                }
+                add(*FIB.get(i)?)?;
             }
+            prev = bit;
             i += 1;
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Fibonacci {
+    data: Bits,
+    index: usize,
+}
+
+impl Fibonacci {
+    pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
+    }
+}
+
 impl Creatable for Fibonacci {
     fn new() -> Self {
         Self {
             data: Bits::new(),
             index: 0,
-            fib: vec![1, 1],
         }
     }
+
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
+        }
+    }
+}
+
+impl CodewordLength for Fibonacci {
+    fn encoded_len(n: u64) -> u32 {
+        // Every Fibonacci number not greater than code, plus the closing bit
+        FIB.partition_point(|f| *f - 1 <= n as u128) as u32 + 1
+    }
 }
 
 impl UniversalCode for Fibonacci {
     fn get(&mut self) -> Option<u64> {
-        let mut n = 0;
-        let mut res = 0_u64;
-        let mut prev = false;
-        loop {
-            match self.data.get(self.index) {
-                None => return None,
-                Some(x) => {
-                    self.index += 1;
-                    n += 1;
-                    if x {
-                        if prev {
-                            return Some(res - 1);
-                        } else {
-                            self.generate_fib(n);
-                            res += self.fib[n];
-                        }
-                    }
-                    prev = x;
-                }
-            }
-        }
+        let mut cursor = BitsCursor::new(&self.data, self.index);
+        let res = FibonacciCode.decode(&mut cursor)?;
+        self.index = cursor.index;
+        Some(res)
     }
 
-    fn add(&mut self, mut code: u64) {
-        code += 1;
-        let mut buffer = vec![true, true];
-        let mut n = self.get_largest_smaller_fib(code);
-        code -= self.fib[n];
-        while code > 0 {
-            let m = self.get_largest_smaller_fib(code);
-            for _ in m..(n - 1) {
-                buffer.push(false);
-            }
-            buffer.push(true);
-            n = m;
-            code -= self.fib[n];
-        }
-        for _ in 1..n {
-            buffer.push(false);
-        }
-        for bit in buffer.into_iter().rev() {
-            self.data.push(bit);
-        }
+    fn decode_into(&mut self, res: &mut Vec<u64>, count: usize) -> usize {
+        decode_bits_into(&FibonacciCode, &self.data, &mut self.index, res, count)
+    }
+
+    fn add(&mut self, code: u64) {
+        // Writing to memory never fails
+        FibonacciCode.encode(code, &mut self.data).unwrap();
     }
 
     fn save_to_file(&self, path: String) -> Result<(), String>{
         self.data.save_to_file(path)
     }
 
+    fn to_bits(&self) -> Bits {
+        self.data.clone()
+    }
+
     fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
         Box::new(UniversalCodeIter {
             c: self
@@ -122,9 +222,11 @@ impl UniversalCode for Fibonacci {
     }
 }
 
+impl_collect!(Fibonacci);
+
 #[cfg(test)]
 mod fib_test {
-    use crate::universal_coding::{UniversalCode, Creatable};
+    use crate::universal_coding::{UniversalCode, Creatable, CodewordLength, boundary_values};
 
     #[test]
     fn fib_test() {
@@ -143,4 +245,64 @@ mod fib_test {
         c.add(0);
         assert_eq!(c.get(), Some(0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn fib_len_test() {
+        let mut c = super::Fibonacci::new();
+        let values: Vec<u64> = (0..1000).chain(boundary_values()).collect();
+        for n in values.iter().copied() {
+            let before = c.len();
+            c.add(n);
+            assert_eq!((c.len() - before) as u32, super::Fibonacci::encoded_len(n), "length of {}", n);
+        }
+        for n in values {
+            assert_eq!(c.get(), Some(n));
+        }
+    }
+
+    #[test]
+    fn fib_table_test() {
+        use crate::bits::Bits;
+        use crate::bit_io::{BitReader, BitsCursor};
+        use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+        use super::FibonacciCode;
+
+        let numbers: Vec<u64> = (0..20000).chain((0..64).map(|i| (1_u64 << i) + 12345)).collect();
+        let mut data = Bits::new();
+        for n in numbers.iter() {
+            FibonacciCode.encode(*n, &mut data).unwrap();
+        }
+        let mut cursor = BitsCursor::new(&data, 0);
+        let mut reader = BitReader::with_limit(data.bytes(), data.len() as u64);
+        for n in numbers.iter() {
+            assert_eq!(FibonacciCode.decode(&mut cursor), Some(*n));
+            assert_eq!(FibonacciCode.decode(&mut reader), Some(*n));
+        }
+        assert_eq!(FibonacciCode.decode(&mut reader), None::<u64>);
+    }
+
+    #[test]
+    fn fib_wide_test() {
+        use crate::bit_io::BitsCursor;
+        use crate::bits::Bits;
+        use crate::unsigned::wide_boundary_values;
+        use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+        use super::FibonacciCode;
+        let mut data = Bits::new();
+        for n in 0..=u8::MAX {
+            FibonacciCode.encode(n, &mut data).unwrap();
+        }
+        for n in wide_boundary_values() {
+            FibonacciCode.encode(n, &mut data).unwrap();
+        }
+        FibonacciCode.encode(256_u16, &mut data).unwrap();
+        let mut cursor = BitsCursor::new(&data, 0);
+        for n in 0..=u8::MAX {
+            assert_eq!(FibonacciCode.decode(&mut cursor), Some(n));
+        }
+        for n in wide_boundary_values() {
+            assert_eq!(FibonacciCode.decode(&mut cursor), Some(n));
+        }
+        assert_eq!(FibonacciCode.decode(&mut cursor), None::<u8>);
+    }
+}
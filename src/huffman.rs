@@ -0,0 +1,373 @@
+use crate::bits::Bits;
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect};
+use crate::unsigned::Unsigned;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::Path;
+
+/**
+    Canonical Huffman code of bit lengths 1..=65, described only by code length of every symbol
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct HuffmanTable {
+    lengths: [u32; HuffmanTable::SYMBOLS],
+    codes: [u64; HuffmanTable::SYMBOLS],
+    count: Vec<u64>,
+    sorted: Vec<usize>,
+}
+
+impl HuffmanTable {
+    const SYMBOLS: usize = 66;
+    const MAX_LENGTH: u32 = 63;
+
+    /**
+        Build optimal code for given number of occurrences of every symbol
+    */
+    pub fn from_counts(counts: &[u64]) -> Self {
+        let mut lengths = [0_u32; Self::SYMBOLS];
+        let mut heap = BinaryHeap::new();
+        let mut leaf_parent = [0_usize; Self::SYMBOLS];
+        let mut parent = vec![];
+        for (s, c) in counts.iter().enumerate().take(Self::SYMBOLS) {
+            if *c > 0 {
+                heap.push(Reverse((*c, s)));
+            }
+        }
+        match heap.len() {
+            0 => return Self::from_lengths(lengths),
+            1 => {
+                let Reverse((_, s)) = heap.pop().unwrap();
+                lengths[s] = 1;
+                return Self::from_lengths(lengths);
+            }
+            _ => {}
+        }
+        // Inner nodes get ids starting from SYMBOLS
+        while heap.len() > 1 {
+            let Reverse((a, x)) = heap.pop().unwrap();
+            let Reverse((b, y)) = heap.pop().unwrap();
+            let id = Self::SYMBOLS + parent.len();
+            parent.push(None);
+            for node in [x, y] {
+                if node >= Self::SYMBOLS {
+                    parent[node - Self::SYMBOLS] = Some(id);
+                } else {
+                    leaf_parent[node] = id;
+                }
+            }
+            heap.push(Reverse((a + b, id)));
+        }
+        let mut depth = vec![0_u32; parent.len()];
+        for node in (0..parent.len()).rev() {
+            if let Some(p) = parent[node] {
+                depth[node] = depth[p - Self::SYMBOLS] + 1;
+            }
+        }
+        for (s, c) in counts.iter().enumerate().take(Self::SYMBOLS) {
+            if *c > 0 {
+                lengths[s] = depth[leaf_parent[s] - Self::SYMBOLS] + 1;
+            }
+        }
+        Self::from_lengths(lengths)
+    }
+
+    /**
+        Assign canonical codes, shorter codes first and equal lengths by symbol
+    */
+    pub fn from_lengths(lengths: [u32; Self::SYMBOLS]) -> Self {
+        let max = lengths.iter().max().copied().unwrap_or(0) as usize;
+        let mut count = vec![0_u64; max + 1];
+        for l in lengths.iter().filter(|l| **l > 0) {
+            count[*l as usize] += 1;
+        }
+        let mut sorted: Vec<usize> = (0..Self::SYMBOLS).filter(|s| lengths[*s] > 0).collect();
+        sorted.sort_by_key(|s| lengths[*s]);
+        let mut codes = [0_u64; Self::SYMBOLS];
+        let mut code = 0_u64;
+        let mut length = 0;
+        for s in sorted.iter() {
+            code <<= lengths[*s] - length;
+            length = lengths[*s];
+            codes[*s] = code;
+            code += 1;
+        }
+        Self {
+            lengths,
+            codes,
+            count,
+            sorted,
+        }
+    }
+
+    pub fn length(&self, symbol: usize) -> u32 {
+        self.lengths[symbol]
+    }
+
+    pub fn write(&self, data: &mut Bits, symbol: usize) {
+        data.push_bits(self.codes[symbol], self.lengths[symbol]);
+    }
+
+    pub fn read(&self, data: &Bits, index: &mut usize) -> Option<usize> {
+        let mut code = 0_u64;
+        let mut first = 0_u64;
+        let mut position = 0_usize;
+        for count in self.count.iter().skip(1) {
+            code |= data.get(*index)? as u64;
+            *index += 1;
+            if code >= first && code - first < *count {
+                return Some(self.sorted[position + (code - first) as usize]);
+            }
+            position += *count as usize;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+
+    /**
+        Store code lengths of symbols up to the last used one
+    */
+    pub fn save(&self, data: &mut Bits) {
+        let last = (0..Self::SYMBOLS).rev().find(|s| self.lengths[*s] > 0).unwrap_or(0);
+        data.push_gamma(last as u64 + 1);
+        for l in self.lengths[..=last].iter() {
+            data.push_gamma(*l as u64 + 1);
+        }
+    }
+
+    pub fn load(data: &Bits, index: &mut usize) -> Option<Self> {
+        let last = data.get_gamma(index)? as usize - 1;
+        if last >= Self::SYMBOLS {
+            return None;
+        }
+        let mut lengths = [0_u32; Self::SYMBOLS];
+        // Kraft sum scaled by 2^MAX_LENGTH, a prefix code never exceeds it
+        let mut kraft = 0_u128;
+        for l in lengths[..=last].iter_mut() {
+            let length = data.get_gamma(index)? - 1;
+            if length > Self::MAX_LENGTH as u64 {
+                return None;
+            }
+            *l = length as u32;
+            if *l > 0 {
+                kraft += 1 << (Self::MAX_LENGTH - *l);
+            }
+        }
+        if kraft > 1 << Self::MAX_LENGTH {
+            return None;
+        }
+        Some(Self::from_lengths(lengths))
+    }
+}
+
+/**
+    Splits every number into bit length and mantissa like Elias gamma,
+    but the bit length is coded with canonical Huffman table stored in front of each block
+*/
+#[derive(Debug)]
+pub struct Huffman {
+    data: Bits,
+    index: usize,
+    block: Vec<u64>,
+    table: Option<HuffmanTable>,
+    remaining: u64,
+}
+
+impl Huffman {
+    const BLOCK_SIZE: usize = 1 << 16;
+
+    pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
+    }
+
+    fn encode_block(data: &mut Bits, block: &[u64]) {
+        let mut counts = [0_u64; HuffmanTable::SYMBOLS];
+        for c in block.iter() {
+            counts[c.successor_size() as usize] += 1;
+        }
+        let table = HuffmanTable::from_counts(&counts);
+        data.push_gamma(block.len() as u64);
+        table.save(data);
+        for c in block.iter() {
+            let n = c.successor_size();
+            table.write(data, n as usize);
+            data.push_bits(c.wrapping_add(1), n - 1);
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.block.is_empty() {
+            Self::encode_block(&mut self.data, &self.block);
+            self.block.clear();
+        }
+    }
+
+    fn closed_data(&self) -> Bits {
+        let mut data = self.data.clone();
+        if !self.block.is_empty() {
+            Self::encode_block(&mut data, &self.block);
+        }
+        data
+    }
+}
+
+impl Creatable for Huffman {
+    fn new() -> Self {
+        Self {
+            data: Bits::new(),
+            index: 0,
+            block: Vec::new(),
+            table: None,
+            remaining: 0,
+        }
+    }
+
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
+            block: Vec::new(),
+            table: None,
+            remaining: 0,
+        }
+    }
+}
+
+impl UniversalCode for Huffman {
+    fn get(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            self.flush();
+            self.remaining = self.data.get_gamma(&mut self.index)?;
+            self.table = Some(HuffmanTable::load(&self.data, &mut self.index)?);
+        }
+        let table = self.table.as_ref()?;
+        let n = table.read(&self.data, &mut self.index)? as u32;
+        if n == 0 {
+            return None;
+        }
+        let low = self.data.get_bits(self.index, n - 1)?;
+        self.index += n as usize - 1;
+        self.remaining -= 1;
+        u64::from_successor(n - 1, low as u128)
+    }
+
+    fn add(&mut self, code: u64) {
+        self.block.push(code);
+        if self.block.len() == Self::BLOCK_SIZE {
+            self.flush();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.flush();
+    }
+
+    fn save_to_file(&self, path: String) -> Result<(), String> {
+        self.closed_data().save_to_file(path)
+    }
+
+    fn to_bits(&self) -> Bits {
+        self.closed_data()
+    }
+
+    fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
+        Box::new(UniversalCodeIter {
+            c: self
+        })
+    }
+
+    fn len(&self) -> usize {
+        if self.block.is_empty() {
+            self.data.len()
+        } else {
+            self.closed_data().len()
+        }
+    }
+
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn entropy(&self) -> f64 {
+        self.closed_data().entropy()
+    }
+}
+
+impl_collect!(Huffman);
+
+#[cfg(test)]
+mod huffman_test {
+    use crate::universal_coding::{UniversalCode, Creatable, boundary_values};
+    use crate::bits::Bits;
+    use super::HuffmanTable;
+
+    #[test]
+    fn huffman_test() {
+        let mut c = super::Huffman::new();
+        c.add(7);
+        assert_eq!(c.get(), Some(7));
+        c.add(15);
+        c.add(31);
+        assert_eq!(c.get(), Some(15));
+        assert_eq!(c.get(), Some(31));
+        assert_eq!(c.get(), None);
+        c.add(1323123213123);
+        c.add(3312312345324423);
+        assert_eq!(c.get(), Some(1323123213123));
+        assert_eq!(c.get(), Some(3312312345324423));
+        c.add(0);
+        assert_eq!(c.get(), Some(0));
+    }
+
+    #[test]
+    fn table_test() {
+        let mut counts = [0_u64; HuffmanTable::SYMBOLS];
+        counts[3] = 40;
+        counts[5] = 30;
+        counts[9] = 20;
+        counts[64] = 10;
+        let table = HuffmanTable::from_counts(&counts);
+        assert_eq!(table.length(3), 1);
+        assert_eq!(table.length(5), 2);
+        assert_eq!(table.length(9), 3);
+        assert_eq!(table.length(64), 3);
+        let mut data = Bits::new();
+        table.save(&mut data);
+        for s in [9, 3, 64, 5] {
+            table.write(&mut data, s);
+        }
+        let mut index = 0;
+        let loaded = HuffmanTable::load(&data, &mut index).unwrap();
+        assert_eq!(loaded, table);
+        for s in [9, 3, 64, 5] {
+            assert_eq!(loaded.read(&data, &mut index), Some(s));
+        }
+    }
+
+    #[test]
+    fn malformed_table_test() {
+        let mut data = Bits::new();
+        data.push_gamma(1);
+        data.push_gamma(65);
+        assert_eq!(HuffmanTable::load(&data, &mut 0), None);
+        let mut data = Bits::new();
+        data.push_gamma(3);
+        for _ in 0..3 {
+            data.push_gamma(2);
+        }
+        assert_eq!(HuffmanTable::load(&data, &mut 0), None);
+    }
+
+    #[test]
+    fn huffman_boundary_test() {
+        let mut c = super::Huffman::new();
+        for n in boundary_values() {
+            c.add(n);
+        }
+        c.finish();
+        for n in boundary_values() {
+            assert_eq!(c.get(), Some(n));
+        }
+        assert_eq!(c.get(), None);
+    }
+}
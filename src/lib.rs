@@ -1,7 +1,24 @@
 pub mod universal_coding;
 pub mod bits;
+pub mod bit_io;
+pub mod unsigned;
 pub mod elias_gamma;
 pub mod elias_delta;
 pub mod elias_omega;
 pub mod fibonacci;
+pub mod arithmetic;
+pub mod rans;
+pub mod huffman;
+pub mod container;
+pub mod blocks;
+pub mod archive;
+pub mod sorted_sequence;
+pub mod elias_fano;
 pub mod dictionary;
+pub mod lzw;
+pub mod lzmw;
+pub mod lzap;
+pub mod algorithm;
+pub mod lz78;
+pub mod lzss;
+pub mod preset;
@@ -0,0 +1,210 @@
+use crate::arithmetic;
+use crate::bits::Bits;
+use crate::container::CodeType;
+use std::collections::HashMap;
+
+/**
+    Coding of the bytes following phrases
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Literals {
+    /**
+        Every byte as 8 bits
+    */
+    Fixed,
+    /**
+        Bytes coded with adaptive arithmetic coder and order-0 byte model
+    */
+    Adaptive,
+}
+
+impl Literals {
+    pub fn id(self) -> u8 {
+        match self {
+            Literals::Fixed => 0,
+            Literals::Adaptive => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        [Literals::Fixed, Literals::Adaptive].iter().copied().find(|l| l.id() == id)
+    }
+}
+
+/**
+    LZ78 parsing into pairs of phrase index and next byte, phrase 0 is empty and every
+    pair defines new phrase. Last pair has no byte when data ends inside a known phrase,
+    so there is one byte less than indices then.
+*/
+pub fn encode(data: &[u8]) -> (Vec<u64>, Vec<u8>) {
+    let mut phrases: HashMap<(u64, u8), u64> = HashMap::new();
+    let mut indices = vec![];
+    let mut literals = vec![];
+    let mut current = 0;
+    for b in data.iter() {
+        match phrases.get(&(current, *b)) {
+            Some(next) => current = *next,
+            None => {
+                phrases.insert((current, *b), phrases.len() as u64 + 1);
+                indices.push(current);
+                literals.push(*b);
+                current = 0;
+            }
+        }
+    }
+    if current != 0 {
+        indices.push(current);
+    }
+    (indices, literals)
+}
+
+pub fn decode(indices: &[u64], literals: &[u8]) -> Result<Vec<u8>, String> {
+    if literals.len() > indices.len() || literals.len() + 1 < indices.len() {
+        return Err("Number of LZ78 literals does not match indices".to_string());
+    }
+    // Parent phrase and last byte of every phrase after the empty one
+    let mut phrases: Vec<(u64, u8)> = vec![(0, 0)];
+    let mut res = vec![];
+    for (i, index) in indices.iter().enumerate() {
+        if *index >= phrases.len() as u64 {
+            return Err(format!("Invalid LZ78 index {}", index));
+        }
+        let start = res.len();
+        let mut p = *index;
+        while p != 0 {
+            res.push(phrases[p as usize].1);
+            p = phrases[p as usize].0;
+        }
+        res[start..].reverse();
+        if let Some(b) = literals.get(i) {
+            res.push(*b);
+            phrases.push((*index, *b));
+        }
+    }
+    Ok(res)
+}
+
+/**
+    File format: magic bytes, version, index coding id, literal coding id,
+    number of coded bits of indices (u64 little endian) and the bits,
+    then literals: raw bytes or number of literals and of coded bits (u64 little endian)
+    and the bits.
+*/
+pub const MAGIC: [u8; 4] = *b"LZ78";
+pub const VERSION: u8 = 2;
+
+pub fn is_lz78(bytes: &[u8]) -> bool {
+    bytes.len() > MAGIC.len() && bytes[..MAGIC.len()] == MAGIC
+}
+
+/**
+    `None` as coding selects the smallest coding of indices
+*/
+pub fn compress(data: &[u8], code: Option<CodeType>, literals: Literals) -> Vec<u8> {
+    let (indices, bytes) = encode(data);
+    let (code, coded) = match code {
+        Some(c) => (c, c.encode(&indices)),
+        None => CodeType::best(&indices),
+    };
+    let coded = coded.to_bits();
+    let mut res = MAGIC.to_vec();
+    res.push(VERSION);
+    res.push(code.id());
+    res.push(literals.id());
+    res.extend_from_slice(&(coded.len() as u64).to_le_bytes());
+    res.extend_from_slice(coded.bytes());
+    match literals {
+        Literals::Fixed => res.extend_from_slice(&bytes),
+        Literals::Adaptive => {
+            let coded = arithmetic::encode_bytes(&bytes);
+            res.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            res.extend_from_slice(&(coded.len() as u64).to_le_bytes());
+            res.extend_from_slice(coded.bytes());
+        }
+    }
+    res
+}
+
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_lz78(bytes) {
+        return Err("Missing LZ78 header".to_string());
+    }
+    let truncated = || "Truncated LZ78 file".to_string();
+    let header = bytes.get(MAGIC.len()..MAGIC.len() + 3).ok_or_else(truncated)?;
+    if header[0] != VERSION {
+        return Err(format!("Unsupported LZ78 version {}", header[0]));
+    }
+    let code = CodeType::from_id(header[1]).ok_or_else(|| format!("Unknown coding {}", header[1]))?;
+    let literals = Literals::from_id(header[2]).ok_or_else(|| format!("Unknown literal coding {}", header[2]))?;
+    let mut position = MAGIC.len() + 3;
+    let read_u64 = |position: &mut usize| -> Result<u64, String> {
+        let size = bytes.get(*position..*position + 8).ok_or_else(truncated)?;
+        let mut len = [0_u8; 8];
+        len.copy_from_slice(size);
+        *position += 8;
+        Ok(u64::from_le_bytes(len))
+    };
+    let read_bits = |position: &mut usize| -> Result<Bits, String> {
+        let len = read_u64(position)? as usize;
+        let data = bytes.get(*position..*position + len.div_ceil(8)).ok_or_else(truncated)?;
+        *position += len.div_ceil(8);
+        Ok(Bits::from_bytes(data.to_vec(), len))
+    };
+    let mut coder = code.from_bits(read_bits(&mut position)?);
+    let indices: Vec<u64> = std::iter::from_fn(|| coder.get()).collect();
+    let literals = match literals {
+        Literals::Fixed => bytes[position..].to_vec(),
+        Literals::Adaptive => {
+            let count = read_u64(&mut position)?;
+            if count > indices.len() as u64 {
+                return Err("Number of LZ78 literals does not match indices".to_string());
+            }
+            arithmetic::decode_bytes(&read_bits(&mut position)?, count as usize).ok_or_else(truncated)?
+        }
+    };
+    decode(&indices, &literals)
+}
+
+#[cfg(test)]
+mod lz78_test {
+    use super::{compress, decode, decompress, encode, Literals};
+    use crate::container::CodeType;
+
+    #[test]
+    fn lz78_test() {
+        let (indices, literals) = encode(b"aababcabcd");
+        assert_eq!(indices, [0, 1, 2, 3]);
+        assert_eq!(literals, b"abcd");
+        let (indices, literals) = encode(b"aba");
+        assert_eq!(indices, [0, 0, 1]);
+        assert_eq!(literals, b"ab");
+        assert_eq!(decode(&indices, &literals), Ok(b"aba".to_vec()));
+        assert_eq!(encode(b""), (vec![], vec![]));
+        assert!(decode(&[0, 5], b"ab").is_err());
+        assert!(decode(&[0], b"ab").is_err());
+    }
+
+    #[test]
+    fn compress_test() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT#".repeat(30);
+        for literals in [Literals::Fixed, Literals::Adaptive] {
+            for code in [Some(CodeType::Gamma), Some(CodeType::Huffman), None] {
+                let file = compress(&data, code, literals);
+                assert!(file.len() < data.len());
+                assert_eq!(decompress(&file), Ok(data.clone()));
+            }
+        }
+        assert_eq!(decompress(&compress(b"", Some(CodeType::Delta), Literals::Adaptive)), Ok(vec![]));
+        let file = compress(&data, Some(CodeType::Omega), Literals::Fixed);
+        assert!(decompress(&file[..20]).is_err());
+    }
+
+    #[test]
+    fn adaptive_test() {
+        let data: Vec<u8> = (0..20000_u32).map(|i| if i * i % 13 < 2 { b'b' } else { b'a' }).collect();
+        let fixed = compress(&data, Some(CodeType::Delta), Literals::Fixed);
+        let adaptive = compress(&data, Some(CodeType::Delta), Literals::Adaptive);
+        assert!(adaptive.len() < fixed.len());
+        assert_eq!(decompress(&adaptive), Ok(data));
+    }
+}
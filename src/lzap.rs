@@ -0,0 +1,53 @@
+use crate::algorithm::{self, PhraseDecoder, PhraseRule};
+use crate::dictionary::Dictionary;
+
+/**
+    LZAP compression: phrases are the longest words of dictionary and previous phrase
+    followed by every prefix of the current one is added, which gives more words to choose
+    from than LZMW.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rule;
+
+impl PhraseRule for Rule {
+    const NAME: &'static str = "LZAP";
+
+    fn update(dictionary: &mut Dictionary, prev: &[u8], phrase: &[u8]) {
+        dictionary.add_extensions(prev, phrase);
+    }
+}
+
+pub type Decoder = PhraseDecoder<Rule>;
+
+/**
+    `progress` is called with every percent of processed data
+*/
+pub fn encode_with_dictionary<F: FnMut(u32)>(data: &[u8], dictionary: &mut Dictionary, progress: F) -> Vec<u64> {
+    algorithm::encode_phrases::<Rule, F>(data, dictionary, progress)
+}
+
+pub fn encode(data: &[u8]) -> Vec<u64> {
+    encode_with_dictionary(data, &mut Dictionary::new(), |_| {})
+}
+
+pub fn decode<I: IntoIterator<Item=u64>>(codes: I) -> Result<Vec<u8>, String> {
+    algorithm::decode_phrases::<Rule, I>(codes)
+}
+
+#[cfg(test)]
+mod lzap_test {
+    use crate::lzw;
+
+    #[test]
+    fn lzap_test() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT#".repeat(40);
+        let codes = super::encode(&data);
+        assert!(codes.len() < lzw::encode(&data).len());
+        let long: Vec<u8> = (0..20000_u32).map(|i| b"abcdefgh"[(i * i / 7 % 8) as usize]).collect();
+        assert_eq!(super::decode(super::encode(&long)), Ok(long));
+        assert_eq!(super::decode(codes), Ok(data));
+        assert_eq!(super::encode(b""), vec![]);
+        assert_eq!(super::decode(vec![]), Ok(vec![]));
+        assert!(super::decode(vec![65, 256, 258]).is_err());
+    }
+}
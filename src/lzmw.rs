@@ -0,0 +1,50 @@
+use crate::algorithm::{self, PhraseDecoder, PhraseRule};
+use crate::dictionary::Dictionary;
+
+/**
+    LZMW compression: phrases are the longest words of dictionary and every new word is
+    previous phrase followed by the current one, so words grow much faster than in LZW.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rule;
+
+impl PhraseRule for Rule {
+    const NAME: &'static str = "LZMW";
+
+    fn update(dictionary: &mut Dictionary, prev: &[u8], phrase: &[u8]) {
+        dictionary.add([prev, phrase].concat());
+    }
+}
+
+pub type Decoder = PhraseDecoder<Rule>;
+
+/**
+    `progress` is called with every percent of processed data
+*/
+pub fn encode_with_dictionary<F: FnMut(u32)>(data: &[u8], dictionary: &mut Dictionary, progress: F) -> Vec<u64> {
+    algorithm::encode_phrases::<Rule, F>(data, dictionary, progress)
+}
+
+pub fn encode(data: &[u8]) -> Vec<u64> {
+    encode_with_dictionary(data, &mut Dictionary::new(), |_| {})
+}
+
+pub fn decode<I: IntoIterator<Item=u64>>(codes: I) -> Result<Vec<u8>, String> {
+    algorithm::decode_phrases::<Rule, I>(codes)
+}
+
+#[cfg(test)]
+mod lzmw_test {
+    use crate::lzw;
+
+    #[test]
+    fn lzmw_test() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT#".repeat(40);
+        let codes = super::encode(&data);
+        assert!(codes.len() < lzw::encode(&data).len());
+        assert_eq!(super::decode(codes), Ok(data));
+        assert_eq!(super::encode(b""), vec![]);
+        assert_eq!(super::decode(vec![]), Ok(vec![]));
+        assert!(super::decode(vec![65, 256, 258]).is_err());
+    }
+}
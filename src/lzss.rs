@@ -0,0 +1,240 @@
+use crate::bits::Bits;
+use crate::container::CodeType;
+
+/**
+    Byte copied to output or copy of `length` bytes starting `offset` bytes back
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Literal(u8),
+    Match { offset: u64, length: u64 },
+}
+
+pub const MIN_MATCH: usize = 3;
+pub const MAX_MATCH: usize = 258;
+pub const WINDOW: usize = 32768;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+const NONE: usize = usize::MAX;
+
+fn hash(data: &[u8]) -> usize {
+    let key = ((data[0] as u32) << 16) | ((data[1] as u32) << 8) | data[2] as u32;
+    (key.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/**
+    LZSS parsing with greedy matches up to `window` bytes back (at most `WINDOW`).
+    Positions with the same hash of the next `MIN_MATCH` bytes are chained, and only `MAX_CHAIN`
+    newest of them are tried. Chain links are kept for the last power of two positions
+    covering the window, older ones are never followed.
+*/
+pub fn encode(data: &[u8], window: usize) -> Vec<Token> {
+    let window = window.clamp(1, WINDOW);
+    let mask = window.next_power_of_two() - 1;
+    let mut head = vec![NONE; 1 << HASH_BITS];
+    let mut prev = vec![NONE; mask + 1];
+    let mut res = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max = (data.len() - i).min(MAX_MATCH);
+            let mut candidate = head[hash(&data[i..])];
+            let mut chain = 0;
+            while candidate != NONE && i - candidate <= window && chain < MAX_CHAIN {
+                let len = data[candidate..].iter().zip(data[i..i + max].iter()).take_while(|(a, b)| a == b).count();
+                if len > best.0 {
+                    best = (len, i - candidate);
+                    if len == max {
+                        break;
+                    }
+                }
+                candidate = prev[candidate & mask];
+                chain += 1;
+            }
+        }
+        let step = if best.0 >= MIN_MATCH {
+            res.push(Token::Match { offset: best.1 as u64, length: best.0 as u64 });
+            best.0
+        } else {
+            res.push(Token::Literal(data[i]));
+            1
+        };
+        for k in i..i + step {
+            if k + MIN_MATCH <= data.len() {
+                let h = hash(&data[k..]);
+                prev[k & mask] = head[h];
+                head[h] = k;
+            }
+        }
+        i += step;
+    }
+    res
+}
+
+pub fn decode(tokens: &[Token]) -> Result<Vec<u8>, String> {
+    let mut res = vec![];
+    for token in tokens.iter() {
+        match *token {
+            Token::Literal(b) => res.push(b),
+            Token::Match { offset, length } => {
+                if offset == 0 || offset > res.len() as u64 {
+                    return Err(format!("Invalid LZSS offset {}", offset));
+                }
+                let start = res.len() - offset as usize;
+                // Copy can overlap the bytes it produces
+                for k in 0..length as usize {
+                    res.push(res[start + k]);
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/**
+    File format: magic bytes, version, flags (one bit per token, set for match),
+    literals and offsets minus one and lengths minus `MIN_MATCH` in two coded streams.
+    Flags are stored as number of bits (u64 little endian) and the bits, literals as
+    their number (u64 little endian) and the bytes, every coded stream as coding id,
+    number of bits (u64 little endian) and the bits.
+*/
+pub const MAGIC: [u8; 4] = *b"LZSS";
+pub const VERSION: u8 = 1;
+
+pub fn is_lzss(bytes: &[u8]) -> bool {
+    bytes.len() > MAGIC.len() && bytes[..MAGIC.len()] == MAGIC
+}
+
+/**
+    `None` as coding selects the smallest coding for offsets and lengths separately
+*/
+pub fn compress(data: &[u8], code: Option<CodeType>, window: usize) -> Vec<u8> {
+    let mut flags = Bits::new();
+    let mut literals = vec![];
+    let mut offsets = vec![];
+    let mut lengths = vec![];
+    for token in encode(data, window) {
+        match token {
+            Token::Literal(b) => {
+                flags.push(false);
+                literals.push(b);
+            }
+            Token::Match { offset, length } => {
+                flags.push(true);
+                offsets.push(offset - 1);
+                lengths.push(length - MIN_MATCH as u64);
+            }
+        }
+    }
+    let mut res = MAGIC.to_vec();
+    res.push(VERSION);
+    res.extend_from_slice(&(flags.len() as u64).to_le_bytes());
+    res.extend_from_slice(flags.bytes());
+    res.extend_from_slice(&(literals.len() as u64).to_le_bytes());
+    res.extend_from_slice(&literals);
+    for numbers in [offsets, lengths] {
+        let (c, coded) = match code {
+            Some(c) => (c, c.encode(&numbers)),
+            None => CodeType::best(&numbers),
+        };
+        let coded = coded.to_bits();
+        res.push(c.id());
+        res.extend_from_slice(&(coded.len() as u64).to_le_bytes());
+        res.extend_from_slice(coded.bytes());
+    }
+    res
+}
+
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_lzss(bytes) {
+        return Err("Missing LZSS header".to_string());
+    }
+    if bytes[MAGIC.len()] != VERSION {
+        return Err(format!("Unsupported LZSS version {}", bytes[MAGIC.len()]));
+    }
+    let truncated = || "Truncated LZSS file".to_string();
+    let mut position = MAGIC.len() + 1;
+    let take = |position: &mut usize, n: usize| -> Result<&[u8], String> {
+        let res = bytes.get(*position..position.saturating_add(n)).ok_or_else(truncated)?;
+        *position += n;
+        Ok(res)
+    };
+    let read_u64 = |position: &mut usize| -> Result<usize, String> {
+        let mut res = [0_u8; 8];
+        res.copy_from_slice(take(position, 8)?);
+        Ok(u64::from_le_bytes(res) as usize)
+    };
+    let flags_len = read_u64(&mut position)?;
+    let flags = Bits::from_bytes(take(&mut position, flags_len.div_ceil(8))?.to_vec(), flags_len);
+    let literals_len = read_u64(&mut position)?;
+    let literals = take(&mut position, literals_len)?;
+    let mut streams = vec![];
+    for _ in 0..2 {
+        let id = take(&mut position, 1)?[0];
+        let code = CodeType::from_id(id).ok_or_else(|| format!("Unknown coding {}", id))?;
+        let len = read_u64(&mut position)?;
+        let data = Bits::from_bytes(take(&mut position, len.div_ceil(8))?.to_vec(), len);
+        let mut coder = code.from_bits(data);
+        streams.push(std::iter::from_fn(move || coder.get()));
+    }
+    let mut tokens = vec![];
+    let mut literals = literals.iter();
+    for i in 0..flags.len() {
+        let token = if flags.get(i) == Some(true) {
+            match (streams[0].next(), streams[1].next()) {
+                (Some(offset), Some(length)) if length <= (MAX_MATCH - MIN_MATCH) as u64 => Token::Match {
+                    offset: offset.saturating_add(1),
+                    length: length + MIN_MATCH as u64,
+                },
+                (Some(_), Some(length)) => return Err(format!("Invalid LZSS length {}", length)),
+                _ => return Err(truncated()),
+            }
+        } else {
+            Token::Literal(*literals.next().ok_or_else(truncated)?)
+        };
+        tokens.push(token);
+    }
+    decode(&tokens)
+}
+
+#[cfg(test)]
+mod lzss_test {
+    use super::{compress, decode, decompress, encode, Token, MAX_MATCH};
+    use crate::container::CodeType;
+
+    #[test]
+    fn lzss_test() {
+        let tokens = encode(b"abcabcabcx", 100);
+        assert_eq!(tokens[..3], [Token::Literal(b'a'), Token::Literal(b'b'), Token::Literal(b'c')]);
+        assert_eq!(tokens[3], Token::Match { offset: 3, length: 6 });
+        assert_eq!(decode(&tokens), Ok(b"abcabcabcx".to_vec()));
+        let zeros = vec![0_u8; 1000];
+        let tokens = encode(&zeros, 100);
+        assert_eq!(tokens[1], Token::Match { offset: 1, length: MAX_MATCH as u64 });
+        assert_eq!(decode(&tokens), Ok(zeros));
+        let data: Vec<u8> = (0..50000_u32).map(|i| (i % 1000 * 7 % 251) as u8).collect();
+        let tokens = encode(&data, 500);
+        for token in tokens.iter() {
+            if let Token::Match { offset, .. } = token {
+                assert!(*offset <= 500);
+            }
+        }
+        assert_eq!(decode(&tokens), Ok(data));
+        assert_eq!(encode(b"ab", 100), [Token::Literal(b'a'), Token::Literal(b'b')]);
+        assert!(decode(&[Token::Match { offset: 1, length: 3 }]).is_err());
+    }
+
+    #[test]
+    fn compress_test() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT#".repeat(30);
+        for code in [Some(CodeType::Gamma), Some(CodeType::Rans), None] {
+            let file = compress(&data, code, 4096);
+            assert!(file.len() * 4 < data.len());
+            assert_eq!(decompress(&file), Ok(data.clone()));
+        }
+        assert_eq!(decompress(&compress(b"", Some(CodeType::Fibonacci), 4096)), Ok(vec![]));
+        let file = compress(&data, Some(CodeType::Delta), 4096);
+        assert!(decompress(&file[..file.len() - 2]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::dictionary::Dictionary;
+
+/**
+    LZW compression of bytes into dictionary indices,
+    `progress` is called with every percent of processed data
+*/
+pub fn encode_with_progress<F: FnMut(u32)>(data: &[u8], progress: F) -> Vec<u64> {
+    encode_with_dictionary(data, &mut Dictionary::new(), progress)
+}
+
+/**
+    LZW compression starting from given dictionary, like the one of a preset,
+    the dictionary is left in its final state with counted uses of words
+*/
+pub fn encode_with_dictionary<F: FnMut(u32)>(data: &[u8], dictionary: &mut Dictionary, mut progress: F) -> Vec<u64> {
+    let mut prev = vec![];
+    let mut res = vec![];
+    let mut percent = 0;
+    for (coded, code) in data.iter().copied().enumerate() {
+        if (coded * 100) / data.len() >= percent {
+            progress(percent as u32);
+            percent += 1;
+        }
+        prev.push(code);
+        if dictionary.word_position(&prev).is_none() {
+            dictionary.add(prev.clone());
+            prev.pop();
+            let index = dictionary.word_position(&prev).unwrap();
+            dictionary.mark_used(index);
+            res.push(index as u64);
+            prev = vec![code];
+        }
+    }
+    // Empty input has no last word
+    if !prev.is_empty() {
+        let index = dictionary.word_position(&prev).unwrap();
+        dictionary.mark_used(index);
+        res.push(index as u64);
+    }
+    res
+}
+
+pub fn encode(data: &[u8]) -> Vec<u64> {
+    encode_with_progress(data, |_| {})
+}
+
+/**
+    Rebuilds dictionary while reading indices, so indices can come in many parts
+*/
+#[derive(Debug, Default)]
+pub struct Decoder {
+    dictionary: Dictionary,
+    prev: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::with_dictionary(Dictionary::new())
+    }
+
+    /**
+        Decoder of codes created by `encode_with_dictionary`
+    */
+    pub fn with_dictionary(dictionary: Dictionary) -> Self {
+        Self {
+            dictionary,
+            prev: vec![],
+        }
+    }
+
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    /**
+        Append bytes of given index to `res`
+    */
+    pub fn push(&mut self, code: u64, res: &mut Vec<u8>) -> Result<(), String> {
+        let invalid = || format!("Invalid LZW code {}", code);
+        if code >= self.dictionary.len() as u64 {
+            // Word which is being defined right now, previous word and its first byte
+            if code > self.dictionary.len() as u64 || self.prev.is_empty() {
+                return Err(invalid());
+            }
+            let mut temp = self.prev.clone();
+            temp.push(self.prev[0]);
+            self.dictionary.add(temp);
+            if code >= self.dictionary.len() as u64 {
+                return Err(invalid());
+            }
+        }
+        self.dictionary.mark_used(code as usize);
+        let word = self.dictionary.word(code as usize);
+        res.extend_from_slice(&word);
+        if !self.prev.is_empty() {
+            let mut temp = self.prev.clone();
+            temp.push(word[0]);
+            self.dictionary.add(temp);
+        }
+        self.prev = word;
+        Ok(())
+    }
+}
+
+pub fn decode<I: IntoIterator<Item=u64>>(codes: I) -> Result<Vec<u8>, String> {
+    let mut decoder = Decoder::new();
+    let mut res = vec![];
+    for code in codes {
+        decoder.push(code, &mut res)?;
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod lzw_test {
+    #[test]
+    fn lzw_test() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT#abababababababab".to_vec();
+        let codes = super::encode(&data);
+        assert!(codes.len() < data.len());
+        assert_eq!(super::decode(codes), Ok(data));
+        assert_eq!(super::encode(b""), vec![]);
+        assert_eq!(super::decode(vec![]), Ok(vec![]));
+        assert!(super::decode(vec![256]).is_err());
+        assert!(super::decode(vec![65, 300]).is_err());
+    }
+}
@@ -1,13 +1,17 @@
-use lzw_with_universal_coder::elias_gamma::EliasGamma;
-use lzw_with_universal_coder::universal_coding::{UniversalCode, Creatable};
-use lzw_with_universal_coder::elias_delta::EliasDelta;
-use lzw_with_universal_coder::elias_omega::EliasOmega;
-use lzw_with_universal_coder::fibonacci::Fibonacci;
+use lzw_with_universal_coder::universal_coding::UniversalCode;
+use lzw_with_universal_coder::algorithm::Algorithm;
+use lzw_with_universal_coder::lz78::{self, Literals};
+use lzw_with_universal_coder::lzss;
+use lzw_with_universal_coder::container::{CodeType, Container};
+use lzw_with_universal_coder::bits::{entropy, Bits, BitOrder};
+use lzw_with_universal_coder::blocks::{self, BlockReader};
+use lzw_with_universal_coder::archive::Archive;
 use lzw_with_universal_coder::dictionary::Dictionary;
+use lzw_with_universal_coder::preset::Preset;
 use std::env;
+use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
-use lzw_with_universal_coder::bits::entropy;
+use std::io::{Read, Seek, SeekFrom, Write};
 
 
 fn print_bar(p: u32) {
@@ -21,137 +25,404 @@ fn print_bar(p: u32) {
     println!("| {}%", p);
 }
 
-fn encode<X, Y>(data: X) -> Y
-    where X: AsRef<[u8]>, Y: UniversalCode + Creatable {
+fn print_usage(program: &str) {
+    println!("Wrong arguments please try {} <--encode | --decode> --type <gamma | delta | omega | fibonacci | arithmetic | rans | huffman | auto> [--block <codes>] [--bit-order <msb | lsb>] [--block-size <bytes>] [--threads <n>] [--range <start>:<len>] [--algorithm <lzw | lzmw | lzap | lz78 | lzss>] [--literals <fixed | adaptive>] [--window <bytes>] [--preset <dictionary>] [--save-dictionary <file>] <file_from> <file_to>", program);
+    println!("or {} --archive <create | extract | list> [--type <code>] [--solid] <archive> [<files> | <directory>]", program);
+    println!("or {} --train [--words <n>] <dictionary> <samples>", program);
+    println!("or {} --dump-dictionary [--algorithm <name>] [--preset <dictionary>] <saved dictionary | file>", program);
+}
+
+fn encode(data: &[u8], algorithm: Algorithm, dictionary: &mut Dictionary) -> Vec<u64> {
     println!("Coding...");
-    let mut prev = vec![];
-    let mut res = Y::new();
-    let data = data.as_ref();
-    let mut dictionary = Dictionary::new();
+    algorithm.encode(data, dictionary, print_bar)
+}
+
+fn decode(sections: &mut [Box<dyn UniversalCode>], algorithm: Algorithm, dictionary: Dictionary) -> Result<Vec<u8>, String> {
+    println!("Decoding...");
+    let mut res = vec![];
+    let mut decoder = algorithm.decoder(dictionary);
     let mut percent = 0;
-    let mut coded = 0;
-    for code in data.iter().map(|a| *a) {
-        if (coded * 100) / data.len() >= percent {
-            print_bar(percent as u32);
-            percent += 1;
-        }
-        prev.push(code);
-        match dictionary.word_position(&prev) {
-            None => {
-                dictionary.add(prev.clone());
-                prev.pop();
-                res.add(dictionary.word_position(&prev).unwrap() as u64);
-                prev = vec![code];
-            }
-            _ => {}
+    let total = sections.iter().fold(0, |acc, s| acc + s.len());
+    let mut done = 0;
+    for data in sections.iter_mut() {
+        while let Some(code) = data.get() {
+            if ((done + data.index()) as f64 * 100.0) / total as f64 > percent as f64 {
+                print_bar(percent);
+                percent += 1;
+            }
+            decoder.push(code, &mut res)?;
         }
-        coded += 1;
+        done += data.len();
     }
-    match dictionary.word_position(&prev) {
-        Some(code) => {
-            res.add(code as u64);
-            res
+    Ok(res)
+}
+
+fn compression_statistics(before: &[u8], after: &[u8]) {
+    println!("Size before {}B", before.len());
+    println!("Size after {}B", after.len());
+    println!("Compression ration {}%", after.len() as f32 * 100.0 / before.len() as f32);
+    println!("Entropy before {}", entropy(before));
+    println!("Entropy after {}", entropy(after));
+}
+
+fn write_output(path: &str, data: &[u8]) -> bool {
+    let mut file;
+    match File::create(path) {
+        Ok(f) => file = f,
+        Err(_error) => {
+            println!("Unable to create file {}", path);
+            return false;
         }
-        _ => res
     }
+    if file.write_all(data).and_then(|_| file.sync_all()).is_err() {
+        println!("Unable to write file {}", path);
+        return false;
+    }
+    true
 }
 
-
-fn decode<X>(data: &mut X) -> Vec<u8>
-    where X: UniversalCode + ?Sized {
-    println!("Decoding...");
-    let mut prev = vec![];
-    let mut res = vec![];
-    let mut dictionary = Dictionary::new();
-    let mut percent = 0;
-    loop {
-        let code;
-        match data.get() {
-            None => return res,
-            Some(c) => {
-                code = c;
-            }
+fn read_preset(path: &str) -> Option<Preset> {
+    let bytes = match fs::read(path) {
+        Ok(b) => b,
+        Err(_e) => {
+            println!("Unable to read file {}", path);
+            return None;
         }
-        if (data.index() as f64 * 100.0) / data.len() as f64 > percent as f64 {
-            print_bar(percent);
-            percent += 1;
+    };
+    match Preset::from_bytes(&bytes) {
+        Ok(p) => Some(p),
+        Err(e) => {
+            println!("{}", e);
+            None
         }
-        if code >= dictionary.len() as u64 {
-            let mut temp = prev.clone();
-            temp.push(prev[0]);
-            dictionary.add(temp);
+    }
+}
+
+fn train(program: &str, words: usize, paths: &[String]) {
+    if paths.len() < 2 {
+        print_usage(program);
+        return;
+    }
+    let mut samples = vec![];
+    for path in paths[1..].iter() {
+        match Archive::read_path(path) {
+            Ok((entries, _skipped)) => samples.extend(entries.into_iter().map(|e| e.content)),
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
         }
-        res.append(&mut dictionary[code as usize].clone());
-        if prev.len() != 0 {
-            let mut temp = prev.clone();
-            temp.push(dictionary[code as usize][0]);
-            dictionary.add(temp);
+    }
+    let preset = Preset::train(&samples, words);
+    if write_output(&paths[0], &preset.to_bytes()) {
+        println!("Trained {} word(s) on {} sample(s)", preset.words().len(), samples.len());
+        println!("Dictionary id {:08x}", preset.id());
+    }
+}
+
+/**
+    Print every word of saved dictionary or of dictionary learned on a file
+*/
+fn dump_dictionary(path: &str, algorithm: Algorithm, mut dictionary: Dictionary) {
+    let bytes = match fs::read(path) {
+        Ok(b) => b,
+        Err(_e) => {
+            println!("Unable to read file {}", path);
+            return;
         }
-        prev = dictionary[code as usize].clone();
+    };
+    if Dictionary::is_dictionary(&bytes) {
+        dictionary = match Dictionary::from_bytes(&bytes) {
+            Ok(d) => d,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+    } else {
+        algorithm.encode(&bytes, &mut dictionary, |_| {});
+    }
+    println!("{:>8} {:>6} {:>10} bytes", "code", "length", "uses");
+    for i in 0..dictionary.len() {
+        let word = dictionary.word(i);
+        let escaped: String = word.iter().flat_map(|b| std::ascii::escape_default(*b)).map(char::from).collect();
+        println!("{:>8} {:>6} {:>10} \"{}\"", i, word.len(), dictionary.uses(i), escaped);
     }
 }
 
-fn compression_statistics<X: UniversalCode + ?Sized>(before: &Vec<u8>, after: &X) {
-    println!("Size before {}B", before.len());
-    println!("Size after {}B", after.len() / 8);
-    println!("Compression ration {}%", (after.len() / 8) as f32 * 100.0 / before.len() as f32);
-    println!("Entropy before {}", entropy(&before));
-    println!("Entropy after {}", after.entropy());
+fn archive(program: &str, operation: char, code: Option<CodeType>, solid: bool, paths: &[String]) {
+    match (operation, paths.len()) {
+        ('c', n) if n >= 2 => {
+            let mut entries = vec![];
+            for path in paths[1..].iter() {
+                match Archive::read_path(path) {
+                    Ok((e, skipped)) => {
+                        entries.extend(e);
+                        for s in skipped.iter() {
+                            println!("Skipped {}", s);
+                        }
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        return;
+                    }
+                }
+            }
+            let archive = if solid {
+                Archive::new_solid(&entries, code)
+            } else {
+                let mut archive = Archive::new();
+                for e in entries.iter() {
+                    archive.add(e.path.clone(), &e.content, e.mtime, e.mode, code);
+                }
+                archive
+            };
+            let bytes = archive.to_bytes();
+            if write_output(&paths[0], &bytes) {
+                let size: u64 = archive.members.iter().map(|m| m.size).sum();
+                println!("Archived {} file(s)", archive.members.len());
+                println!("Size before {}B", size);
+                println!("Size after {}B", bytes.len());
+            }
+        }
+        ('x', 1) | ('x', 2) | ('l', 1) => {
+            let bytes = match fs::read(&paths[0]) {
+                Ok(b) => b,
+                Err(_e) => {
+                    println!("Unable to read file {}", paths[0]);
+                    return;
+                }
+            };
+            let archive = match Archive::from_bytes(&bytes) {
+                Ok(a) => a,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            if operation == 'l' {
+                for m in archive.members.iter() {
+                    println!("{:o} {:>12} {:>12} {:<10} {}", m.mode, m.size, m.mtime, m.code.name(), m.path);
+                }
+            } else if let Err(e) = archive.extract_to(paths.get(1).map(|p| p.as_str()).unwrap_or(".")) {
+                println!("{}", e);
+            }
+        }
+        _ => print_usage(program),
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    /*let code: char = 'f';
-    let operation: char = 'd';
-    let path_from: String = "code.code".parse().unwrap();
-    let path_to: String = "new.bmp".parse().unwrap();*/
-    let code: char;
-    let operation: char;
-    let path_from: String;
-    let path_to: String;
-    match args.len() {
-        4 => {
-            match args[1].as_str() {
-                "--encode" => operation = 'e',
-                "--decode" => operation = 'd',
-                _ => {
-                    println!("Wrong arguments please try {} <--encode | --decode> --type <gamma | delta | omega | fibonacci> <file_from> <file_to>", args[0]);
-                    return;
+    let mut operation: Option<char> = None;
+    // None stands for automatic selection
+    let mut code: Option<CodeType> = Some(CodeType::Omega);
+    let mut block: Option<usize> = None;
+    let mut order = BitOrder::MsbFirst;
+    let mut block_size: Option<usize> = None;
+    let mut range: Option<(u64, u64)> = None;
+    let mut threads: Option<usize> = None;
+    let mut solid = false;
+    let mut words = 4096;
+    let mut preset: Option<String> = None;
+    let mut save_dictionary: Option<String> = None;
+    let mut algorithm = Algorithm::Lzw;
+    let mut lz78 = false;
+    let mut lzss = false;
+    let mut window: Option<usize> = None;
+    let mut literals: Option<Literals> = None;
+    let mut paths: Vec<String> = vec![];
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--encode" => operation = Some('e'),
+            "--decode" => operation = Some('d'),
+            "--solid" => solid = true,
+            "--train" => operation = Some('t'),
+            "--dump-dictionary" => operation = Some('u'),
+            "--algorithm" => {
+                i += 1;
+                match args.get(i).map(|s| s.as_str()) {
+                    Some("lz78") => lz78 = true,
+                    Some("lzss") => lzss = true,
+                    Some(name) if Algorithm::from_name(name).is_some() => algorithm = Algorithm::from_name(name).unwrap(),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
                 }
             }
-            code = 'o';
-            path_from = args[2].clone();
-            path_to = args[3].clone();
-        }
-        6 => {
-            match args[1].as_str() {
-                "--encode" => operation = 'e',
-                "--decode" => operation = 'd',
-                _ => {
-                    println!("Wrong arguments please try {} <--encode | --decode> --type <gamma | delta | omega | fibonacci> <file_from> <file_to>", args[0]);
-                    return;
+            "--window" => {
+                i += 1;
+                match args.get(i).map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) if (1..=lzss::WINDOW).contains(&n) => window = Some(n),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
                 }
             }
-            match args[3].as_str() {
-                "gamma" => code = 'g',
-                "delta" => code = 'd',
-                "omega" => code = 'o',
-                "fibonacci" => code = 'f',
-                _ => {
-                    println!("Wrong arguments please try {} <--encode | --decode> --type <gamma | delta | omega | fibonacci> <file_from> <file_to>", args[0]);
-                    return;
+            "--literals" => {
+                i += 1;
+                match args.get(i).map(|s| s.as_str()) {
+                    Some("fixed") => literals = Some(Literals::Fixed),
+                    Some("adaptive") => literals = Some(Literals::Adaptive),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--save-dictionary" => {
+                i += 1;
+                match args.get(i) {
+                    Some(p) => save_dictionary = Some(p.clone()),
+                    None => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--words" => {
+                i += 1;
+                match args.get(i).map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) => words = n,
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
                 }
             }
-            path_from = args[4].clone();
-            path_to = args[5].clone();
+            "--preset" => {
+                i += 1;
+                match args.get(i) {
+                    Some(p) => preset = Some(p.clone()),
+                    None => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--archive" => {
+                i += 1;
+                match args.get(i).map(|s| s.as_str()) {
+                    Some("create") => operation = Some('c'),
+                    Some("extract") => operation = Some('x'),
+                    Some("list") => operation = Some('l'),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--type" => {
+                i += 1;
+                match args.get(i).map(|s| s.as_str()) {
+                    Some("auto") => code = None,
+                    Some(name) if CodeType::from_name(name).is_some() => code = CodeType::from_name(name),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--block" => {
+                i += 1;
+                match args.get(i).map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => block = Some(n),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--bit-order" => {
+                i += 1;
+                match args.get(i).map(|s| s.as_str()) {
+                    Some("msb") => order = BitOrder::MsbFirst,
+                    Some("lsb") => order = BitOrder::LsbFirst,
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--block-size" => {
+                i += 1;
+                match args.get(i).map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => block_size = Some(n),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--threads" => {
+                i += 1;
+                match args.get(i).map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => threads = Some(n),
+                    _ => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            "--range" => {
+                i += 1;
+                let parsed = args.get(i).and_then(|s| s.split_once(':')).and_then(|(start, len)| {
+                    Some((start.parse::<u64>().ok()?, len.parse::<u64>().ok()?))
+                });
+                match parsed {
+                    Some(r) => range = Some(r),
+                    None => {
+                        print_usage(&args[0]);
+                        return;
+                    }
+                }
+            }
+            _ => paths.push(args[i].clone()),
         }
-        _ => {
-            println!("Wrong arguments please try {} <--encode | --decode> --type <gamma | delta | omega | fibonacci> <file_from> <file_to>", args[0]);
+        i += 1;
+    }
+    // Only the LZW container stores bit order
+    let lsb = order == BitOrder::LsbFirst;
+    if let Some(op @ ('c' | 'x' | 'l')) = operation {
+        if lsb {
+            print_usage(&args[0]);
             return;
         }
+        archive(&args[0], op, code, solid, &paths);
+        return;
+    }
+    if operation == Some('t') {
+        train(&args[0], words, &paths);
+        return;
     }
+    let blocks_mode = block_size.is_some() || threads.is_some();
+    let files = if operation == Some('u') { 1 } else { 2 };
+    let lzw_options = block.is_some() || preset.is_some() || save_dictionary.is_some() || algorithm != Algorithm::Lzw;
+    let lsb_other = lsb && (blocks_mode || lz78 || lzss || operation == Some('u'));
+    let dump_other = operation == Some('u') && (lz78 || lzss);
+    if operation.is_none() || paths.len() != files || (blocks_mode && lzw_options) || lsb_other || dump_other || ((lz78 || lzss) && (blocks_mode || lzw_options)) || (lz78 && lzss) || (literals.is_some() && !lz78) || (window.is_some() && !lzss) {
+        print_usage(&args[0]);
+        return;
+    }
+    let preset = match preset {
+        Some(path) => match read_preset(&path) {
+            Some(p) => Some(p),
+            None => return,
+        },
+        None => None,
+    };
+    let dictionary = || preset.as_ref().map(|p| p.dictionary()).unwrap_or_default();
+    if operation == Some('u') {
+        dump_dictionary(&paths[0], algorithm, dictionary());
+        return;
+    }
+    let path_from = paths[0].clone();
+    let path_to = paths[1].clone();
     match operation {
-        'e' => {
+        Some('e') => {
             let mut file;
             match File::open(path_from.clone()) {
                 Ok(f) => file = f,
@@ -161,102 +432,165 @@ fn main() {
                 }
             }
             let mut data = vec![];
-            match file.read_to_end(data.as_mut()) {
-                Err(_e) => {
-                    println!("Unable to read file {}", path_from);
-                    return;
+            if let Err(_e) = file.read_to_end(data.as_mut()) {
+                println!("Unable to read file {}", path_from);
+                return;
+            }
+            if lz78 || lzss {
+                let bytes = if lz78 {
+                    lz78::compress(&data, code, literals.unwrap_or(Literals::Fixed))
+                } else {
+                    lzss::compress(&data, code, window.unwrap_or(lzss::WINDOW))
+                };
+                if write_output(&path_to, &bytes) {
+                    compression_statistics(&data, &bytes);
                 }
-                Ok(_) => {}
-            }
-            let coded_data: Box<dyn UniversalCode>;
-            match code {
-                'g' => coded_data = Box::new(encode::<&Vec<u8>, EliasGamma>(&data)),
-                'd' => coded_data = Box::new(encode::<&Vec<u8>, EliasDelta>(&data)),
-                'o' => coded_data = Box::new(encode::<&Vec<u8>, EliasOmega>(&data)),
-                'f' => coded_data = Box::new(encode::<&Vec<u8>, Fibonacci>(&data)),
-                _ => {
-                    println!("Critical error unknown coding");
-                    return;
+                return;
+            }
+            if blocks_mode {
+                // Independent blocks with index for random access, one block per thread by default
+                let threads = threads.unwrap_or(1);
+                let size = block_size.unwrap_or_else(|| data.len().div_ceil(threads));
+                let bytes = blocks::compress_blocks_parallel(&data, code, size, threads);
+                if write_output(&path_to, &bytes) {
+                    compression_statistics(&data, &bytes);
+                }
+                return;
+            }
+            let mut learned = dictionary();
+            let codes = encode(&data, algorithm, &mut learned);
+            if let Some(path) = &save_dictionary {
+                write_output(path, &learned.to_bytes());
+            }
+            let chunks: Vec<&[u64]> = match block {
+                Some(n) => codes.chunks(n).collect(),
+                None => vec![codes.as_slice()],
+            };
+            let mut container = Container::new();
+            container.order = order;
+            container.preset = preset.as_ref().map(|p| p.id());
+            container.algorithm = algorithm;
+            let mut selected = [0_usize; CodeType::ALL.len()];
+            for chunk in chunks {
+                let (c, coded_data) = match code {
+                    Some(c) => (c, c.encode(chunk)),
+                    None => CodeType::best(chunk),
+                };
+                selected[c.id() as usize] += 1;
+                container.push(c, coded_data.to_bits());
+            }
+            if code.is_none() {
+                for c in CodeType::ALL.iter() {
+                    if selected[c.id() as usize] > 0 {
+                        println!("Selected {} coding for {} block(s)", c.name(), selected[c.id() as usize]);
+                    }
                 }
             }
-            match coded_data.save_to_file(path_to) {
+            match container.save_to_file(path_to) {
                 Ok(()) => {
-                    compression_statistics(&data, &*coded_data);
+                    compression_statistics(&data, &container.to_bytes());
                 }
                 Err(e) => {
                     println!("{}", e);
-                    return;
                 }
             }
         }
-        'd' => {
-            let mut coded_data: Box<dyn UniversalCode>;
-            match code {
-                'g' => {
-                    match EliasGamma::read_from_file(path_from.clone()) {
-                        Ok(r) => coded_data = Box::new(r),
-                        Err(_) => {
-                            println!("Unable to read file {}", path_from);
-                            return;
-                        }
-                    }
+        Some('d') => {
+            let mut file;
+            match File::open(path_from.clone()) {
+                Ok(f) => file = f,
+                Err(_error) => {
+                    println!("Unable to open file {}", path_from.clone());
+                    return;
                 }
-                'd' => {
-                    match EliasDelta::read_from_file(path_from.clone()) {
-                        Ok(r) => coded_data = Box::new(r),
-                        Err(_) => {
-                            println!("Unable to read file {}", path_from);
-                            return;
-                        }
+            }
+            let mut magic = [0_u8; 5];
+            let is_block_file = file.read_exact(&mut magic).is_ok() && blocks::is_block_file(&magic);
+            if file.seek(SeekFrom::Start(0)).is_err() {
+                println!("Unable to read file {}", path_from);
+                return;
+            }
+            if is_block_file {
+                let data = BlockReader::new(file).and_then(|mut reader| {
+                    reader.threads = threads.unwrap_or(1);
+                    match range {
+                        Some((start, len)) => reader.read_range(start, len),
+                        None => reader.read_all(),
+                    }
+                });
+                match data {
+                    Ok(d) => {
+                        write_output(&path_to, &d);
                     }
+                    Err(e) => println!("{}", e),
                 }
-                'o' => {
-                    match EliasOmega::read_from_file(path_from.clone()) {
-                        Ok(r) => coded_data = Box::new(r),
-                        Err(_) => {
-                            println!("Unable to read file {}", path_from);
-                            return;
-                        }
+                return;
+            }
+            if range.is_some() {
+                println!("Range can be read only from files encoded with --block-size");
+                return;
+            }
+            let mut bytes = vec![];
+            if let Err(_e) = file.read_to_end(bytes.as_mut()) {
+                println!("Unable to read file {}", path_from);
+                return;
+            }
+            if lz78::is_lz78(&bytes) || lzss::is_lzss(&bytes) {
+                let data = if lz78::is_lz78(&bytes) {
+                    lz78::decompress(&bytes)
+                } else {
+                    lzss::decompress(&bytes)
+                };
+                match data {
+                    Ok(d) => {
+                        write_output(&path_to, &d);
                     }
+                    Err(e) => println!("{}", e),
                 }
-                'f' => {
-                    match Fibonacci::read_from_file(path_from.clone()) {
-                        Ok(r) => coded_data = Box::new(r),
-                        Err(_) => {
-                            println!("Unable to read file {}", path_from);
+                return;
+            }
+            let mut coded_data: Vec<Box<dyn UniversalCode>> = if Container::is_container(&bytes) {
+                match Container::from_bytes(&bytes) {
+                    Ok(container) => {
+                        if container.preset != preset.as_ref().map(|p| p.id()) {
+                            match container.preset {
+                                Some(id) => println!("File is coded with preset dictionary {:08x}", id),
+                                None => println!("File is coded without preset dictionary"),
+                            }
                             return;
                         }
+                        algorithm = container.algorithm;
+                        container.sections.into_iter()
+                            .map(|s| s.code.from_bits(s.data))
+                            .collect()
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        return;
                     }
                 }
-                _ => {
-                    println!("Critical error unknown coding");
-                    return;
-                }
-            }
-            let data = decode(&mut *coded_data);
-            let mut file;
-            match File::create(path_to.clone()) {
-                Ok(f) => file = f,
-                Err(_error) => {
-                    println!("Unable to create file {}", path_to.clone());
-                    return;
-                }
-            }
-            match file.write_all(data.as_ref()) {
-                Err(_e) => {
-                    println!("Unable to write file {}", path_to.clone());
-                    return;
+            } else {
+                // Plain stream, coding has to be given by user
+                match code {
+                    Some(c) => {
+                        let size = bytes.len() * 8;
+                        vec![c.from_bits(Bits::from_bytes(bytes, size))]
+                    }
+                    None => {
+                        println!("Unable to detect coding of file {}", path_from);
+                        return;
+                    }
                 }
-                Ok(_) => {}
-            }
-            match file.sync_all() {
-                Err(_e) => {
-                    println!("Unable to write file {}", path_to);
+            };
+            let data = match decode(&mut coded_data, algorithm, dictionary()) {
+                Ok(d) => d,
+                Err(e) => {
+                    println!("{}", e);
                     return;
                 }
-                Ok(_) => {}
-            }
+            };
+            write_output(&path_to, &data);
         }
         _ => {}//panic("Wrong operation")
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,146 @@
+use crate::dictionary::Dictionary;
+use crate::lzw;
+use std::collections::BTreeSet;
+
+/**
+    Words loaded into the dictionary before coding, so short inputs similar to the
+    training samples are compressed from the first byte.
+    File format: magic bytes, version, number of words (u32) and every word as
+    its length (u32) and bytes. All numbers are little endian.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preset {
+    words: Vec<Vec<u8>>,
+}
+
+impl Preset {
+    pub const MAGIC: [u8; 4] = *b"LZWP";
+    pub const VERSION: u8 = 1;
+
+    /**
+        Learn dictionary on all samples and keep up to `max_words` words saving the most bytes,
+        together with their prefixes
+    */
+    pub fn train(samples: &[Vec<u8>], max_words: usize) -> Self {
+        let data: Vec<u8> = samples.concat();
+        let mut dictionary = Dictionary::new();
+        lzw::encode_with_dictionary(&data, &mut dictionary, |_| {});
+        let uses = |i: usize| dictionary.uses(i) as usize;
+        let mut candidates: Vec<usize> = (256..dictionary.len()).filter(|i| uses(*i) > 0).collect();
+        candidates.sort_by_key(|i| (std::cmp::Reverse(uses(*i) * dictionary.word_len(*i)), *i));
+        let mut selected = BTreeSet::new();
+        for i in candidates {
+            // Prefixes of a word are always in dictionary
+            let mut word = dictionary.word(i);
+            let mut missing = vec![];
+            while word.len() > 1 {
+                let index = dictionary.word_position(&word).unwrap();
+                if selected.contains(&index) {
+                    break;
+                }
+                missing.push(index);
+                word.pop();
+            }
+            if selected.len() + missing.len() > max_words {
+                continue;
+            }
+            selected.extend(missing);
+        }
+        // Prefixes have smaller indices, so they are added first
+        Self {
+            words: selected.into_iter().map(|i| dictionary.word(i)).collect(),
+        }
+    }
+
+    pub fn words(&self) -> &[Vec<u8>] {
+        &self.words
+    }
+
+    /**
+        Dictionary with all single bytes followed by words of the preset
+    */
+    pub fn dictionary(&self) -> Dictionary {
+        let mut res = Dictionary::new();
+        for word in self.words.iter() {
+            res.add(word.clone());
+        }
+        res
+    }
+
+    /**
+        FNV-1a hash of the serialized preset, stored in headers of files coded with it
+    */
+    pub fn id(&self) -> u32 {
+        self.to_bytes().iter().fold(0x811c9dc5_u32, |h, b| (h ^ *b as u32).wrapping_mul(0x01000193))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Self::MAGIC.to_vec();
+        res.push(Self::VERSION);
+        res.extend_from_slice(&(self.words.len() as u32).to_le_bytes());
+        for word in self.words.iter() {
+            res.extend_from_slice(&(word.len() as u32).to_le_bytes());
+            res.extend_from_slice(word);
+        }
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() <= Self::MAGIC.len() || bytes[..Self::MAGIC.len()] != Self::MAGIC {
+            return Err("Missing preset dictionary header".to_string());
+        }
+        if bytes[Self::MAGIC.len()] != Self::VERSION {
+            return Err(format!("Unsupported preset dictionary version {}", bytes[Self::MAGIC.len()]));
+        }
+        let mut position = Self::MAGIC.len() + 1;
+        let read_u32 = |position: &mut usize| -> Result<usize, String> {
+            if bytes.len() - *position < 4 {
+                return Err("Truncated preset dictionary".to_string());
+            }
+            let mut res = [0_u8; 4];
+            res.copy_from_slice(&bytes[*position..*position + 4]);
+            *position += 4;
+            Ok(u32::from_le_bytes(res) as usize)
+        };
+        let count = read_u32(&mut position)?;
+        let mut words = vec![];
+        for _ in 0..count {
+            let len = read_u32(&mut position)?;
+            if bytes.len() - position < len {
+                return Err("Truncated preset dictionary".to_string());
+            }
+            words.push(bytes[position..position + len].to_vec());
+            position += len;
+        }
+        Ok(Self { words })
+    }
+}
+
+#[cfg(test)]
+mod preset_test {
+    use super::Preset;
+    use crate::lzw;
+
+    #[test]
+    fn preset_test() {
+        let samples: Vec<Vec<u8>> = (0..50).map(|i| {
+            format!("{{\"user\": \"user{}\", \"action\": \"login\", \"status\": \"ok\"}}", i).into_bytes()
+        }).collect();
+        let preset = Preset::train(&samples, 500);
+        assert!(!preset.words().is_empty() && preset.words().len() <= 500);
+        let message = b"{\"user\": \"user77\", \"action\": \"login\", \"status\": \"ok\"}".to_vec();
+        let codes = lzw::encode_with_dictionary(&message, &mut preset.dictionary(), |_| {});
+        assert!(codes.len() * 3 < lzw::encode(&message).len());
+        let mut decoder = lzw::Decoder::with_dictionary(preset.dictionary());
+        let mut res = vec![];
+        for code in codes {
+            decoder.push(code, &mut res).unwrap();
+        }
+        assert_eq!(res, message);
+        let read = Preset::from_bytes(&preset.to_bytes()).unwrap();
+        assert_eq!(read, preset);
+        assert_eq!(read.id(), preset.id());
+        assert_ne!(Preset::train(&samples[..1], 500).id(), preset.id());
+        assert!(Preset::from_bytes(&preset.to_bytes()[..20]).is_err());
+    }
+}
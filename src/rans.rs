@@ -0,0 +1,290 @@
+use crate::bits::Bits;
+use crate::universal_coding::{UniversalCode, UniversalCodeIter, Creatable, impl_collect};
+use crate::unsigned::Unsigned;
+use std::path::Path;
+
+/**
+    Static rANS coder for LZW indices working on blocks of numbers.
+    Every block starts with its size and a table of normalized frequencies
+    of bit lengths, followed by rANS state, rANS words and raw low bits.
+*/
+#[derive(Debug)]
+pub struct Rans {
+    data: Bits,
+    index: usize,
+    block: Vec<u64>,
+    decoded: Vec<u64>,
+}
+
+impl Rans {
+    const BLOCK_SIZE: usize = 1 << 16;
+    const PROB_BITS: u32 = 12;
+    const PROB_SCALE: u64 = 1 << Self::PROB_BITS;
+    const RANS_L: u64 = 1 << 16;
+    const WORD_BITS: u32 = 16;
+    const SYMBOLS: usize = 66;
+
+    pub fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
+        Ok(Self::from_bits(Bits::read_from_file(path)?))
+    }
+
+    /**
+        Scale counts of symbols, so they sum up to `PROB_SCALE` and every used symbol stays nonzero
+    */
+    fn normalize(counts: &[u64; Self::SYMBOLS]) -> [u64; Self::SYMBOLS] {
+        let sum: u64 = counts.iter().sum();
+        let mut freq = [0_u64; Self::SYMBOLS];
+        let mut largest = 0;
+        for (s, c) in counts.iter().enumerate() {
+            if *c > 0 {
+                freq[s] = (c * Self::PROB_SCALE / sum).max(1);
+                if freq[s] > freq[largest] {
+                    largest = s;
+                }
+            }
+        }
+        let total: u64 = freq.iter().sum();
+        freq[largest] = freq[largest] + Self::PROB_SCALE - total;
+        freq
+    }
+
+    fn cumulative(freq: &[u64; Self::SYMBOLS]) -> [u64; Self::SYMBOLS + 1] {
+        let mut cum = [0_u64; Self::SYMBOLS + 1];
+        for s in 0..Self::SYMBOLS {
+            cum[s + 1] = cum[s] + freq[s];
+        }
+        cum
+    }
+
+    fn encode_block(data: &mut Bits, block: &[u64]) {
+        let mut counts = [0_u64; Self::SYMBOLS];
+        let sizes: Vec<usize> = block.iter().map(|c| c.successor_size() as usize).collect();
+        for s in sizes.iter() {
+            counts[*s] += 1;
+        }
+        let freq = Self::normalize(&counts);
+        let cum = Self::cumulative(&freq);
+
+        let mut words = vec![];
+        let mut x = Self::RANS_L;
+        for s in sizes.iter().rev() {
+            let x_max = ((Self::RANS_L >> Self::PROB_BITS) << Self::WORD_BITS) * freq[*s];
+            if x >= x_max {
+                words.push(x & 0xffff);
+                x >>= Self::WORD_BITS;
+            }
+            x = ((x / freq[*s]) << Self::PROB_BITS) + x % freq[*s] + cum[*s];
+        }
+
+        data.push_gamma(block.len() as u64);
+        let max_symbol = sizes.iter().max().copied().unwrap_or(1);
+        data.push_gamma(max_symbol as u64);
+        for f in freq[1..=max_symbol].iter() {
+            data.push_gamma(f + 1);
+        }
+        data.push_gamma(words.len() as u64 + 1);
+        data.push_bits(x, 2 * Self::WORD_BITS);
+        for w in words.into_iter().rev() {
+            data.push_bits(w, Self::WORD_BITS);
+        }
+        for (c, s) in block.iter().zip(sizes.iter()) {
+            data.push_bits(c.wrapping_add(1), *s as u32 - 1);
+        }
+    }
+
+    fn decode_block(data: &Bits, index: &mut usize) -> Option<Vec<u64>> {
+        let count = data.get_gamma(index)? as usize;
+        let max_symbol = data.get_gamma(index)? as usize;
+        if max_symbol >= Self::SYMBOLS {
+            return None;
+        }
+        let mut freq = [0_u64; Self::SYMBOLS];
+        for f in freq[1..=max_symbol].iter_mut() {
+            *f = data.get_gamma(index)? - 1;
+        }
+        let cum = Self::cumulative(&freq);
+        if cum[Self::SYMBOLS] != Self::PROB_SCALE {
+            return None;
+        }
+        let mut slots = vec![0_usize; Self::PROB_SCALE as usize];
+        for s in 0..Self::SYMBOLS {
+            for slot in slots[cum[s] as usize..cum[s + 1] as usize].iter_mut() {
+                *slot = s;
+            }
+        }
+        let words = data.get_gamma(index)? as usize - 1;
+        let mut word_index = *index;
+        let mut raw_index = words
+            .checked_add(2)?
+            .checked_mul(Self::WORD_BITS as usize)?
+            .checked_add(*index)?;
+        if raw_index > data.len() {
+            return None;
+        }
+        let mut x = data.get_bits(word_index, 2 * Self::WORD_BITS)?;
+        word_index += 2 * Self::WORD_BITS as usize;
+
+        let mut res = Vec::with_capacity(count.min(data.len()));
+        for _ in 0..count {
+            let slot = x & (Self::PROB_SCALE - 1);
+            let s = slots[slot as usize];
+            x = freq[s] * (x >> Self::PROB_BITS) + slot - cum[s];
+            if x < Self::RANS_L {
+                x = (x << Self::WORD_BITS) | data.get_bits(word_index, Self::WORD_BITS)?;
+                word_index += Self::WORD_BITS as usize;
+            }
+            let low = data.get_bits(raw_index, s as u32 - 1)?;
+            raw_index += s - 1;
+            res.push(u64::from_successor(s as u32 - 1, low as u128)?);
+        }
+        *index = raw_index;
+        res.reverse();
+        Some(res)
+    }
+
+    fn flush(&mut self) {
+        if !self.block.is_empty() {
+            Self::encode_block(&mut self.data, &self.block);
+            self.block.clear();
+        }
+    }
+
+    fn closed_data(&self) -> Bits {
+        let mut data = self.data.clone();
+        if !self.block.is_empty() {
+            Self::encode_block(&mut data, &self.block);
+        }
+        data
+    }
+}
+
+impl Creatable for Rans {
+    fn new() -> Self {
+        Self {
+            data: Bits::new(),
+            index: 0,
+            block: Vec::new(),
+            decoded: Vec::new(),
+        }
+    }
+
+    fn from_bits(data: Bits) -> Self {
+        Self {
+            data,
+            index: 0,
+            block: Vec::new(),
+            decoded: Vec::new(),
+        }
+    }
+}
+
+impl UniversalCode for Rans {
+    fn get(&mut self) -> Option<u64> {
+        if self.decoded.is_empty() {
+            self.flush();
+            self.decoded = Self::decode_block(&self.data, &mut self.index)?;
+        }
+        self.decoded.pop()
+    }
+
+    fn add(&mut self, code: u64) {
+        self.block.push(code);
+        if self.block.len() == Self::BLOCK_SIZE {
+            self.flush();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.flush();
+    }
+
+    fn save_to_file(&self, path: String) -> Result<(), String> {
+        self.closed_data().save_to_file(path)
+    }
+
+    fn to_bits(&self) -> Bits {
+        self.closed_data()
+    }
+
+    fn into_iter(self) -> Box<dyn Iterator<Item=u64>> {
+        Box::new(UniversalCodeIter {
+            c: self
+        })
+    }
+
+    fn len(&self) -> usize {
+        if self.block.is_empty() {
+            self.data.len()
+        } else {
+            self.closed_data().len()
+        }
+    }
+
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn entropy(&self) -> f64 {
+        self.closed_data().entropy()
+    }
+}
+
+impl_collect!(Rans);
+
+#[cfg(test)]
+mod rans_test {
+    use crate::universal_coding::{UniversalCode, Creatable, boundary_values};
+
+    #[test]
+    fn rans_test() {
+        let mut c = super::Rans::new();
+        c.add(7);
+        assert_eq!(c.get(), Some(7));
+        c.add(15);
+        c.add(31);
+        assert_eq!(c.get(), Some(15));
+        assert_eq!(c.get(), Some(31));
+        assert_eq!(c.get(), None);
+        c.add(1323123213123);
+        c.add(3312312345324423);
+        assert_eq!(c.get(), Some(1323123213123));
+        assert_eq!(c.get(), Some(3312312345324423));
+        c.add(0);
+        assert_eq!(c.get(), Some(0));
+    }
+
+    #[test]
+    fn rans_blocks_test() {
+        let mut c = super::Rans::new();
+        let n = super::Rans::BLOCK_SIZE as u64 * 2 + 100;
+        for i in 0..n {
+            c.add(i * 7919 % 5000);
+        }
+        c.finish();
+        for i in 0..n {
+            assert_eq!(c.get(), Some(i * 7919 % 5000));
+        }
+        assert_eq!(c.get(), None);
+    }
+
+    #[test]
+    fn rans_boundary_test() {
+        let mut c = super::Rans::new();
+        for n in boundary_values() {
+            c.add(n);
+        }
+        c.finish();
+        for n in boundary_values() {
+            assert_eq!(c.get(), Some(n));
+        }
+        assert_eq!(c.get(), None);
+    }
+
+    #[test]
+    fn collect_test() {
+        let mut c: super::Rans = (0..100).collect();
+        c.extend([u64::MAX, 7]);
+        let decoded: Vec<u64> = c.into_iter().collect();
+        assert_eq!(decoded, (0..100).chain([u64::MAX, 7]).collect::<Vec<u64>>());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::bits::Bits;
+use crate::bit_io::BitsCursor;
+use crate::universal_coding::{UniversalEncoder, UniversalDecoder};
+
+/**
+    Position in stream right after entry `index`, which has given value
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Skip {
+    value: u64,
+    index: usize,
+    position: usize,
+}
+
+/**
+    Non decreasing numbers (like posting lists) stored as gaps coded with chosen universal code.
+    Every `skip_every` entries a skip pointer is kept in memory, so `advance_to` does not decode
+    everything before the target.
+*/
+#[derive(Debug, Clone)]
+pub struct SortedSequence<C> {
+    code: C,
+    data: Bits,
+    skip_every: usize,
+    skips: Vec<Skip>,
+    len: usize,
+    last: u64,
+}
+
+impl<C: UniversalEncoder + UniversalDecoder> SortedSequence<C> {
+    /**
+        `skip_every` equal to 0 disables skip pointers
+    */
+    pub fn new(code: C, skip_every: usize) -> Self {
+        Self {
+            code,
+            data: Bits::new(),
+            skip_every,
+            skips: Vec::new(),
+            len: 0,
+            last: 0,
+        }
+    }
+
+    pub fn from_sorted(code: C, skip_every: usize, values: &[u64]) -> Result<Self, String> {
+        let mut res = Self::new(code, skip_every);
+        for v in values {
+            res.push(*v)?;
+        }
+        Ok(res)
+    }
+
+    /**
+        Sequence of `len` numbers from gaps coded as in `bits`, skip pointers are rebuilt
+        and bits after the last number are dropped
+    */
+    pub fn from_bits(code: C, skip_every: usize, data: Bits, len: usize) -> Result<Self, String> {
+        let mut res = Self::new(code, skip_every);
+        let mut cursor = BitsCursor::new(&data, 0);
+        for _ in 0..len {
+            let gap: u64 = match res.code.decode(&mut cursor) {
+                Some(g) => g,
+                None => return Err("Truncated sequence".to_string()),
+            };
+            let value = match res.last.checked_add(gap) {
+                Some(v) => v,
+                None => return Err("Damaged sequence".to_string()),
+            };
+            res.record(value, cursor.index);
+        }
+        res.data = data.slice(0, cursor.index).unwrap();
+        Ok(res)
+    }
+
+    pub fn push(&mut self, value: u64) -> Result<(), String> {
+        if value < self.last {
+            return Err(format!("Value {} is smaller than previous {}", value, self.last));
+        }
+        // Writing to memory never fails
+        self.code.encode(value - self.last, &mut self.data).unwrap();
+        self.record(value, self.data.len());
+        Ok(())
+    }
+
+    /**
+        Count value coded right before `position`
+    */
+    fn record(&mut self, value: u64, position: usize) {
+        self.last = value;
+        self.len += 1;
+        if self.skip_every > 0 && self.len.is_multiple_of(self.skip_every) {
+            self.skips.push(Skip {
+                value,
+                index: self.len - 1,
+                position,
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /**
+        Coded gaps, skip pointers are not part of it and `from_bits` rebuilds them
+    */
+    pub fn bits(&self) -> &Bits {
+        &self.data
+    }
+
+    pub fn iter(&self) -> SortedIter<'_, C> {
+        SortedIter {
+            sequence: self,
+            cursor: BitsCursor::new(&self.data, 0),
+            index: 0,
+            value: 0,
+        }
+    }
+}
+
+/**
+    Reads sequence from the beginning, can jump forward with `advance_to`
+*/
+#[derive(Debug)]
+pub struct SortedIter<'a, C> {
+    sequence: &'a SortedSequence<C>,
+    cursor: BitsCursor<'a>,
+    // Number of entries read so far
+    index: usize,
+    value: u64,
+}
+
+impl<'a, C: UniversalEncoder + UniversalDecoder> SortedIter<'a, C> {
+    /**
+        First not yet read value greater or equal to target
+    */
+    pub fn advance_to(&mut self, target: u64) -> Option<u64> {
+        // Entries up to the last skip below target are all smaller than it
+        let k = self.sequence.skips.partition_point(|s| s.value < target);
+        if k > 0 {
+            let skip = self.sequence.skips[k - 1];
+            if skip.index >= self.index {
+                self.cursor.index = skip.position;
+                self.index = skip.index + 1;
+                self.value = skip.value;
+            }
+        }
+        self.find(|v| *v >= target)
+    }
+}
+
+impl<'a, C: UniversalEncoder + UniversalDecoder> Iterator for SortedIter<'a, C> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.sequence.len {
+            return None;
+        }
+        let gap: u64 = self.sequence.code.decode(&mut self.cursor)?;
+        self.value += gap;
+        self.index += 1;
+        Some(self.value)
+    }
+}
+
+#[cfg(test)]
+mod sorted_sequence_test {
+    use super::SortedSequence;
+    use crate::bits::Bits;
+    use crate::elias_gamma::GammaCode;
+    use crate::fibonacci::FibonacciCode;
+
+    #[test]
+    fn sorted_sequence_test() {
+        let values: Vec<u64> = (0..1000).map(|i| i * i / 7).chain([u64::MAX - 1, u64::MAX]).collect();
+        let sequence = SortedSequence::from_sorted(GammaCode, 16, &values).unwrap();
+        assert_eq!(sequence.len(), values.len());
+        assert_eq!(sequence.iter().collect::<Vec<u64>>(), values);
+        let mut sequence = SortedSequence::new(FibonacciCode, 0);
+        sequence.push(5).unwrap();
+        assert!(sequence.push(4).is_err());
+        sequence.push(5).unwrap();
+        assert_eq!(sequence.iter().collect::<Vec<u64>>(), [5, 5]);
+    }
+
+    #[test]
+    fn advance_test() {
+        let values: Vec<u64> = (0..5000).map(|i| i * 10 + i % 7).collect();
+        let sequence = SortedSequence::from_sorted(FibonacciCode, 32, &values).unwrap();
+        let mut iter = sequence.iter();
+        let mut position = 0;
+        for target in [0, 1, 2, 100, 100, 5000, 5001, 9000, 49980, 49991] {
+            let expected = values[position..].iter().copied().find(|v| *v >= target);
+            assert_eq!(iter.advance_to(target), expected, "target {}", target);
+            position = values.iter().position(|v| Some(*v) == expected).unwrap() + 1;
+        }
+        assert_eq!(iter.advance_to(60000), None);
+
+        let stored = Bits::from_bytes(sequence.bits().bytes().to_vec(), sequence.bits().len() + 5);
+        let mut read = SortedSequence::from_bits(FibonacciCode, 32, stored, values.len()).unwrap();
+        assert_eq!(read.bits().len(), sequence.bits().len());
+        assert_eq!(read.skips, sequence.skips);
+        assert_eq!(read.iter().advance_to(49981), Some(49991));
+        read.push(60000).unwrap();
+        assert_eq!(read.iter().last(), Some(60000));
+        assert!(SortedSequence::from_bits(FibonacciCode, 32, Bits::new(), 1).is_err());
+    }
+}
@@ -1,15 +1,144 @@
+use crate::bits::Bits;
+use crate::bit_io::{BitRead, BitWrite, BitsCursor};
+use crate::unsigned::Unsigned;
+use std::io;
+
+/**
+    Writes codewords of numbers of type `T` into any bit sink
+*/
+pub trait UniversalEncoder<T: Unsigned = u64> {
+    fn encode<W: BitWrite + ?Sized>(&self, n: T, w: &mut W) -> io::Result<()>;
+}
+
+/**
+    Reads codewords from any bit source, `None` at the end of data
+    or when the number does not fit in `T`
+*/
+pub trait UniversalDecoder<T: Unsigned = u64> {
+    fn decode<R: BitRead + ?Sized>(&self, r: &mut R) -> Option<T>;
+}
+
 pub trait UniversalCode{
     fn get(&mut self) -> Option<u64>;
     fn add(&mut self, code: u64);
+    /**
+        Called after the last `add`, for codes that need to close the stream
+    */
+    fn finish(&mut self) {}
     fn save_to_file(&self, path: String) -> Result<(), String>;
+    /**
+        Finished stream of all added numbers
+    */
+    fn to_bits(&self) -> Bits;
     fn into_iter(self) -> Box<dyn Iterator<Item=u64>>;
     fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     fn index(&self) -> usize;
     fn entropy(&self) -> f64;
+
+    /**
+        Signed numbers are stored zig-zag mapped: 0, -1, 1, -2, 2, ...
+    */
+    fn add_signed(&mut self, code: i64) {
+        self.add(zigzag(code));
+    }
+
+    fn get_signed(&mut self) -> Option<i64> {
+        self.get().map(unzigzag)
+    }
+
+    /**
+        Add all numbers with a single dynamic call
+    */
+    fn encode_slice(&mut self, codes: &[u64]) {
+        for c in codes {
+            self.add(*c);
+        }
+    }
+
+    /**
+        Append up to `count` next numbers to `res`, returns how many were read
+    */
+    fn decode_into(&mut self, res: &mut Vec<u64>, count: usize) -> usize {
+        let before = res.len();
+        res.reserve(count);
+        while res.len() - before < count {
+            match self.get() {
+                Some(c) => res.push(c),
+                None => break,
+            }
+        }
+        res.len() - before
+    }
+}
+
+/**
+    Map signed number to unsigned one, so numbers close to zero stay small
+*/
+pub fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+pub fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
 }
 
 pub trait Creatable{
     fn new() -> Self;
+    fn from_bits(data: Bits) -> Self;
+}
+
+/**
+    `decode_into` for codes stored as plain bits: one cursor for all numbers,
+    `index` moves only after whole codewords
+*/
+pub fn decode_bits_into<D: UniversalDecoder>(code: &D, data: &Bits, index: &mut usize, res: &mut Vec<u64>, count: usize) -> usize {
+    let mut cursor = BitsCursor::new(data, *index);
+    let before = res.len();
+    res.reserve(count);
+    while res.len() - before < count {
+        match code.decode(&mut cursor) {
+            Some(c) => res.push(c),
+            None => break,
+        }
+        *index = cursor.index;
+    }
+    res.len() - before
+}
+
+/**
+    `Extend` and `FromIterator` over `add` for a `UniversalCode + Creatable`
+*/
+macro_rules! impl_collect {
+    ($code:ty) => {
+        impl Extend<u64> for $code {
+            fn extend<I: IntoIterator<Item=u64>>(&mut self, iter: I) {
+                for c in iter {
+                    $crate::universal_coding::UniversalCode::add(self, c);
+                }
+            }
+        }
+
+        impl std::iter::FromIterator<u64> for $code {
+            fn from_iter<I: IntoIterator<Item=u64>>(iter: I) -> Self {
+                let mut res = <Self as $crate::universal_coding::Creatable>::new();
+                res.extend(iter);
+                res
+            }
+        }
+    };
+}
+
+pub(crate) use impl_collect;
+
+/**
+    Codes where length of codeword depends only on the coded number,
+    so size of the stream can be computed without encoding
+*/
+pub trait CodewordLength: UniversalCode {
+    fn encoded_len(n: u64) -> u32;
 }
 
 
@@ -23,4 +152,43 @@ impl<X: UniversalCode+ ?Sized> Iterator for UniversalCodeIter<X>{
     fn next(&mut self) -> Option<Self::Item> {
         self.c.get()
     }
-}
\ No newline at end of file
+}
+
+/**
+    Numbers next to powers of two and Fibonacci numbers, where codeword lengths change
+*/
+#[cfg(test)]
+pub(crate) fn boundary_values() -> Vec<u64> {
+    let mut edges = vec![u64::MAX];
+    edges.extend((0..64).map(|k| 1_u64 << k));
+    let (mut a, mut b) = (1_u64, 2_u64);
+    while let Some(c) = a.checked_add(b) {
+        edges.push(b);
+        a = b;
+        b = c;
+    }
+    edges.push(b);
+    let mut res: Vec<u64> = edges.into_iter()
+        .flat_map(|e| [e.saturating_sub(2), e.saturating_sub(1), e, e.saturating_add(1)])
+        .collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+#[cfg(test)]
+mod universal_coding_test {
+    use super::{zigzag, unzigzag};
+
+    #[test]
+    fn zigzag_test() {
+        assert_eq!(zigzag(0), 0);
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+        assert_eq!(zigzag(i64::MAX), u64::MAX - 1);
+        assert_eq!(zigzag(i64::MIN), u64::MAX);
+        for n in [0, 1, -1, 63, -64, i64::MAX, i64::MIN, i64::MIN + 1] {
+            assert_eq!(unzigzag(zigzag(n)), n);
+        }
+    }
+}
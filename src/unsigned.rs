@@ -0,0 +1,135 @@
+use crate::bit_io::{BitRead, BitWrite};
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::io;
+
+/**
+    Unsigned integer types which can be coded with static universal codes,
+    values are handled through u128 so one implementation serves every width
+*/
+pub trait Unsigned: Copy + Debug + Default + Eq + Ord {
+    const BITS: u32;
+
+    fn to_u128(self) -> u128;
+
+    /**
+        `None` when value does not fit in the type
+    */
+    fn from_u128(n: u128) -> Option<Self>;
+
+    /**
+        Bit length of `self + 1`, codes shift numbers by one so zero can be coded
+    */
+    fn successor_size(self) -> u32 {
+        match self.to_u128().checked_add(1) {
+            Some(m) => 128 - m.leading_zeros(),
+            None => 129,
+        }
+    }
+
+    /**
+        Number with `self + 1 = 2^size + low`, `low` has `size` bits
+    */
+    fn from_successor(size: u32, low: u128) -> Option<Self> {
+        if size > Self::BITS {
+            return None;
+        }
+        if size == 128 {
+            // 2^128 - 1 is the only such number that fits in u128
+            return if low == 0 { Self::from_u128(u128::MAX) } else { None };
+        }
+        Self::from_u128(((1 << size) | low) - 1)
+    }
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl Unsigned for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                fn to_u128(self) -> u128 {
+                    self as u128
+                }
+
+                fn from_u128(n: u128) -> Option<Self> {
+                    <$t>::try_from(n).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+
+/**
+    Write `n` lowest bits of value (up to 128), most significant first
+*/
+pub fn write_wide<W: BitWrite + ?Sized>(w: &mut W, value: u128, n: u32) -> io::Result<()> {
+    if n > 64 {
+        w.write_bits((value >> 64) as u64, n - 64)?;
+        w.write_bits(value as u64, 64)
+    } else {
+        w.write_bits(value as u64, n)
+    }
+}
+
+/**
+    Read `n` bits (up to 128), most significant first
+*/
+pub fn read_wide<R: BitRead + ?Sized>(r: &mut R, n: u32) -> Option<u128> {
+    if n > 64 {
+        let high = r.read_bits(n - 64)? as u128;
+        Some(high << 64 | r.read_bits(64)? as u128)
+    } else {
+        r.read_bits(n).map(|v| v as u128)
+    }
+}
+
+/**
+    Numbers next to powers of two in u128
+*/
+#[cfg(test)]
+pub(crate) fn wide_boundary_values() -> Vec<u128> {
+    let mut res: Vec<u128> = (0..128)
+        .map(|k| 1_u128 << k)
+        .flat_map(|e| [e - 1, e, e + 1])
+        .chain([u128::MAX - 1, u128::MAX])
+        .collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+#[cfg(test)]
+mod unsigned_test {
+    use super::{Unsigned, read_wide, write_wide};
+    use crate::bit_io::BitsCursor;
+    use crate::bits::Bits;
+
+    #[test]
+    fn successor_test() {
+        assert_eq!(0_u8.successor_size(), 1);
+        assert_eq!(u8::MAX.successor_size(), 9);
+        assert_eq!(u128::MAX.successor_size(), 129);
+        assert_eq!(u8::from_successor(8, 0), Some(u8::MAX));
+        assert_eq!(u8::from_successor(8, 1), None);
+        assert_eq!(u128::from_successor(128, 0), Some(u128::MAX));
+        assert_eq!(u128::from_successor(128, 1), None);
+        assert_eq!(u16::from_u128(70000), None);
+    }
+
+    #[test]
+    fn wide_bits_test() {
+        let mut data = Bits::new();
+        let value = 0x8123_4567_89ab_cdef_0123_4567_89ab_cdef_u128;
+        write_wide(&mut data, value, 128).unwrap();
+        write_wide(&mut data, 5, 3).unwrap();
+        write_wide(&mut data, value, 100).unwrap();
+        let mut cursor = BitsCursor::new(&data, 0);
+        assert_eq!(read_wide(&mut cursor, 128), Some(value));
+        assert_eq!(read_wide(&mut cursor, 3), Some(5));
+        assert_eq!(read_wide(&mut cursor, 100), Some(value & ((1 << 100) - 1)));
+        assert_eq!(read_wide(&mut cursor, 1), None);
+    }
+}